@@ -1,13 +1,48 @@
 use std::borrow::Borrow;
+use std::cmp;
 use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::io;
 use std::mem;
 use std::marker::PhantomData;
 use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use num_traits::{Bounded, ToPrimitive, FromPrimitive};
 use stable_deref_trait::StableDeref;
 
-use traits::Map;
+use traits::{Map, NewMap, Interner};
+
+mod bulk;
+mod cons;
+mod frozen;
+mod hash_index;
+mod hashed;
+mod hits;
+mod iter;
+mod lru;
+#[cfg(feature = "memmap")]
+mod mapped;
+mod memory;
+mod refcount;
+mod retain;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod snapshot;
+mod sorted;
+
+pub use self::cons::ConsTable;
+pub use self::frozen::FrozenArenaSet;
+pub use self::hashed::HashedArenaSet;
+pub use self::iter::{Iter, Ids, Values, IntoIter};
+pub use self::lru::LruArenaSet;
+#[cfg(feature = "memmap")]
+pub use self::mapped::MappedArenaSet;
+pub use self::memory::MemoryUsage;
+pub use self::refcount::Symbol;
+pub use self::retain::Drain;
+pub use self::snapshot::Blob;
 
 /// An efficient, generic internment structure.
 ///
@@ -80,12 +115,75 @@ use traits::Map;
 ///     assert_eq!(p.intern("two"), Ok(Small(1)));
 ///     assert_eq!(p.intern("three"), Ok(Small(2)));
 ///     assert_eq!(p.intern("four"), Ok(Small(3)));
-///     assert_eq!(p.intern("fail"), Err(Error::IdOverflow));
+///     assert_eq!(p.intern("fail"), Err(Error::IdOverflow { max_idx: 3 }));
 ///     assert_eq!(p.disintern(Small(0)), Ok("one".into()));
 ///     assert_eq!(p.intern("success"), Ok(Small(0)));
 /// }
 /// ```
 ///
+/// The first ID handed out is the minimum value of `I`, so sets with disjoint
+/// ranges can share one ID space. IDs below the minimum fail to resolve with
+/// [`Error::FromIdFailed`].
+///
+/// ```
+/// #[macro_use] extern crate shawshank;
+/// extern crate num_traits;
+///
+/// use shawshank::Error;
+///
+/// custom_intern_id!(Id, u32, 1000, 1999);
+///
+/// fn main() {
+///     let mut p = shawshank::Builder::<String, Id>::new().hash().unwrap();
+///     assert_eq!(p.intern("one"), Ok(Id(1000)));
+///     assert_eq!(p.intern("two"), Ok(Id(1001)));
+///     assert_eq!(p.resolve(Id(1001)), Ok("two"));
+///     assert_eq!(p.resolve::<_, str>(Id(1)), Err(Error::FromIdFailed));
+///     assert_eq!(p.disintern(Id(1000)), Ok("one".into()));
+///     assert_eq!(p.resolve::<_, str>(Id(1000)), Err(Error::InvalidId { index: 0 }));
+///     assert_eq!(p.max_idx(), 999);
+/// }
+/// ```
+///
+/// # Generational IDs
+///
+/// By default, the index of a disinterned item is reused by the next call to
+/// [`intern`], so a stale ID silently resolves to whatever took its place. A
+/// set built with [`Builder::generational`] instead reserves the high bits of
+/// each ID for a per-slot generation, which is bumped whenever the slot is
+/// vacated. Stale IDs are then rejected with [`Error::StaleId`]. Generations
+/// wrap around, so this only guards against the last `2^bits - 1` reuses of a
+/// slot; in exchange, the number of unique IDs shrinks by the same factor.
+///
+/// ```
+/// use shawshank::Error;
+///
+/// let mut p = shawshank::builder::<String>().generational(8).hash().unwrap();
+/// let hello = p.intern("hello").unwrap();
+/// assert_eq!(p.disintern(hello), Ok("hello".into()));
+/// let world = p.intern("world").unwrap();
+/// assert!(world != hello);
+/// assert_eq!(p.resolve::<_, str>(hello), Err(Error::StaleId { index: 0 }));
+/// assert_eq!(p.disintern(hello), Err(Error::StaleId { index: 0 }));
+/// assert_eq!(p.resolve(world), Ok("world"));
+/// ```
+///
+/// # Snapshots
+///
+/// Sets of [`Blob`]s, i.e. `String` and `Vec<u8>`, can be saved in a compact,
+/// versioned binary format with [`write_to`], and loaded with [`read_from`].
+/// All integers are little-endian, and the layout is:
+///
+///   * A 40-byte header: the magic bytes `SHWK`, a `u16` version, a `u8`
+///     item kind, a `u8` number of generation bits, and the `u64`s maximum
+///     index, number of slots, number of generations and length of the blob
+///     region.
+///   * The slot table: the length of each item as a `u64`, with `!0` marking
+///     vacant slots.
+///   * The generations, as `u64`s.
+///   * The blob region: the bytes of each item, back to back.
+///   * A `u64` FNV-1a checksum of everything before it.
+///
 /// # Type Parameters
 ///
 ///   * `O`: The "owened" type of interned items (e.g. `String`, `Vec<T>`).
@@ -96,21 +194,32 @@ use traits::Map;
 /// [`Error::FromIdFailed`]: enum.Error.html#variant.FromIdFailed
 /// [`Error::ToIdFailed`]: enum.Error.html#variant.ToIdFailed
 /// [`Error::IdOverflow`]: enum.Error.html#variant.IdOverflow
+/// [`Error::StaleId`]: enum.Error.html#variant.StaleId
+/// [`Builder::generational`]: struct.Builder.html#method.generational
+/// [`Blob`]: trait.Blob.html
+/// [`write_to`]: struct.ArenaSet.html#method.write_to
+/// [`read_from`]: struct.ArenaSet.html#method.read_from
 /// [`Map`]: trait.Map.html
 /// [`custom_intern_id!`]: macro.custom_intern_id.html
 /// [`vec_arena`]: https://github.com/stjepang/vec-arena
 pub struct ArenaSet<O: StableDeref, I = usize, M = HashMap<&'static < O as Deref >::Target, I>> {
+    // declared first, so that it's dropped before the items its keys borrow
     map: M,
     interned: Vec<Slot<O>>,
     head: usize,
     max_idx: usize,
+    packing: Packing,
+    generations: Vec<usize>,
+    refcounts: Option<Vec<usize>>,
+    hits: Option<Vec<AtomicUsize>>,
+    sorted: Option<Sorted<O, I, M>>,
     _i: PhantomData<I>,
 }
 
 impl<O, I, M> ArenaSet<O, I, M>
 where O: StableDeref,
       I: Bounded + ToPrimitive + FromPrimitive,
-      M: Map {
+      M: NewMap {
     /// Create a new, empty ArenaSet.
     #[inline]
     pub fn new() -> Result<Self, Error> {
@@ -120,29 +229,110 @@ where O: StableDeref,
     /// Create a new, empty ArenaSet with a capacity hint.
     #[inline]
     pub fn with_capacity(capacity: usize) -> Result<Self, Error> {
-        Self::bounded_with_capacity(
-            I::max_value().to_usize().ok_or(Error::FromIdFailed)? -
-                I::min_value().to_usize().ok_or(Error::FromIdFailed)?,
-            capacity)
+        Self::bounded_with_capacity(Self::max_possible_idx()?, capacity)
     }
 
     /// Create a new, empty ArenaSet with a specific maximum index and a capacity hint.
     #[inline]
     pub fn bounded_with_capacity(max_idx: usize, capacity: usize) -> Result<Self, Error> {
-        let max_possible = I::max_value().to_usize().ok_or(Error::FromIdFailed)?
-            - I::min_value().to_usize().ok_or(Error::FromIdFailed)?;
+        let max_possible = Self::max_possible_idx()?;
         if max_idx > max_possible {
-            return Err(Error::IdOverflow);
+            return Err(Error::IdOverflow { max_idx: max_possible });
+        }
+        let mut p = Self::with_map(M::with_capacity(capacity))?;
+        p.max_idx = max_idx;
+        p.interned.reserve(capacity);
+        Ok(p)
+    }
+
+    /// Create a new, empty ArenaSet whose IDs carry a generation of
+    /// `generation_bits` bits, with a capacity hint.
+    ///
+    /// The remaining bits of the ID type hold the index, so the maximum index
+    /// is reduced accordingly. If `generation_bits` is zero, this is the same
+    /// as [`with_capacity`].
+    ///
+    /// ```
+    /// use shawshank::{ArenaSet, Error};
+    ///
+    /// let p = ArenaSet::<String, u8>::generational_with_capacity(6, 0).unwrap();
+    /// assert_eq!(p.max_idx(), 3);
+    /// assert!(ArenaSet::<String, u8>::generational_with_capacity(8, 0).is_err());
+    /// ```
+    ///
+    /// [`with_capacity`]: struct.ArenaSet.html#method.with_capacity
+    pub fn generational_with_capacity(generation_bits: u32, capacity: usize) -> Result<Self, Error> {
+        let mut p = Self::generational_with_map(generation_bits, M::with_capacity(capacity))?;
+        p.interned.reserve(capacity);
+        if generation_bits != 0 {
+            p.generations.reserve(capacity);
         }
+        Ok(p)
+    }
+}
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      I: Bounded + ToPrimitive + FromPrimitive,
+      M: Map {
+    /// Create a new, empty ArenaSet that uses `map`, e.g. a `HashMap` with a
+    /// particular hasher. Unlike the other constructors, this doesn't need
+    /// the map to implement [`NewMap`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `map` isn't empty.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use std::collections::hash_map::RandomState;
+    /// use shawshank::ArenaSet;
+    ///
+    /// let hasher = RandomState::new();
+    /// let mut p = ArenaSet::<String>::with_map(HashMap::with_hasher(hasher)).unwrap();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// ```
+    ///
+    /// [`NewMap`]: trait.NewMap.html
+    pub fn with_map(map: M) -> Result<Self, Error> {
+        assert!(map.len() == 0, "an ArenaSet can only be created with an empty map");
         Ok(ArenaSet {
-            map: M::with_capacity(capacity),
-            max_idx: max_idx,
+            map,
+            max_idx: Self::max_possible_idx()?,
             head: !0,
-            interned: Vec::with_capacity(capacity),
+            interned: Vec::new(),
+            packing: Packing::plain::<I>()?,
+            generations: Vec::new(),
+            refcounts: None,
+            hits: None,
+            sorted: None,
             _i: PhantomData,
         })
     }
 
+    /// Analogue of [`generational_with_capacity`] for [`with_map`].
+    ///
+    /// [`generational_with_capacity`]: struct.ArenaSet.html#method.generational_with_capacity
+    /// [`with_map`]: struct.ArenaSet.html#method.with_map
+    pub fn generational_with_map(generation_bits: u32, map: M) -> Result<Self, Error> {
+        let mut p = Self::with_map(map)?;
+        if generation_bits != 0 {
+            p.packing = Packing::generational::<I>(generation_bits)?;
+            p.max_idx = p.packing.max_idx::<I>()?;
+        }
+        Ok(p)
+    }
+
+    /// Get the maximum index, i.e. one less than the number of unique IDs.
+    #[inline]
+    pub fn max_idx(&self) -> usize {
+        self.max_idx
+    }
+
+    fn max_possible_idx() -> Result<usize, Error> {
+        Packing::plain::<I>()?.max_idx::<I>()
+    }
+
     /// Get the number of interned items.
     ///
     /// ```
@@ -182,111 +372,99 @@ where O: StableDeref,
     pub fn resolve<'a, U, Q: ? Sized>(&'a self, id: U) -> Result<&'a Q, Error>
         where U: Borrow<I>,
               O: Borrow<Q> {
-        let ix = id.borrow().to_usize().ok_or(Error::FromIdFailed)?;
-        let owned = self.interned.get(ix).ok_or(Error::InvalidId)?;
-        match *owned {
+        let ix = self.index_of(id.borrow())?;
+        self.add_hits(ix, 1);
+        match self.interned[ix] {
             Slot::Occupied(ref item) => Ok(item.borrow()),
-            _ => Err(Error::InvalidId)
+            // invariant: `index_of` only returns indices of occupied slots.
+            Slot::Vacant(_) => unreachable!(),
         }
     }
-}
 
-// couldn't figure out how to get traits to abstract the differences
-// between ArenaSet and StadiumSet, so had to resort to macros
+    // Convert a slot index to an ID, tagged with the slot's generation.
+    #[inline]
+    fn to_id(&self, ix: usize) -> Result<I, Error> {
+        I::from_usize(self.packing.pack(ix, &self.generations)).ok_or(Error::ToIdFailed)
+    }
 
-macro_rules! insert {
-    ( $this:ident, $item:ident, $to_owned:expr ) => { {
-        // fast case: item already interned
-        if let Some(entry) = $this.map.get(make_static($item.borrow())) {
-            return Ok(*entry);
-        }
-        // don't let IDs overflow
-        let cnt = $this.count();
-        if cnt != 0 && cnt - 1 == $this.max_idx {
-            return Err(Error::IdOverflow);
-        }
-        let owned = $to_owned($item);
-        let reference = make_static(owned.deref());
-        let ix =
-        if $this.head == !0 {
-            // invariant: no vacant slots
-            $this.interned.push(Slot::Occupied(owned));
-            $this.interned.len() - 1
-        } else {
-            // invariant: if `self.head != !0`, then it has an
-            // index to vacant slot.
-            let ix = $this.head;
-            if let Slot::Vacant(next) = mem::replace(unsafe { $this.interned.get_unchecked_mut(ix) },
-                                                     Slot::Occupied(owned)) {
-                $this.head = next;
-                ix
-            } else {
-                unreachable!()
-            }
-        };
-        // convert to ID
-        match I::from_usize(ix).ok_or(Error::ToIdFailed) {
-            Ok(id) => {
-                // complete internment
-                $this.map.insert(reference, id);
-                Ok(id)
-            }
-            Err(err) => {
-                // revert internment.
-                // invariant: something was just placed at `ix`,
-                // so we don't need to check the access; and
-                // `self.head` has been updated correctly.
-                *unsafe { $this.interned.get_unchecked_mut(ix) } = Slot::Vacant($this.head);
-                $this.head = ix;
-                Err(err)
+    // Convert an ID to the index of the occupied slot it refers to.
+    #[inline]
+    fn index_of(&self, id: &I) -> Result<usize, Error> {
+        self.packing.locate(id.to_usize().ok_or(Error::FromIdFailed)?, &self.interned, &self.generations)
+    }
+
+    // Rebuild the free list from scratch, lowest index first.
+    fn relink_vacant(&mut self) {
+        self.head = !0;
+        for (ix, slot) in self.interned.iter_mut().enumerate().rev() {
+            if let Slot::Vacant(ref mut next) = *slot {
+                *next = self.head;
+                self.head = ix;
             }
         }
-    } }
-}
+    }
 
-macro_rules! disintern {
-    ( $this:expr, $id:ident) => { {
-        let ix = $id.borrow().to_usize().ok_or(Error::FromIdFailed)?;
-        match $this.interned.get_mut(ix) {
-            None => Err(Error::InvalidId),
-            Some(&mut Slot::Vacant(_)) => Err(Error::InvalidId),
-            Some(occupied) => {
-                // invariant: we just eliminated all other possibilities, so we know
-                // it's occupied; and `self.head` has been updated correctly.
-                if let Slot::Occupied(item) = mem::replace(occupied, Slot::Vacant($this.head)) {
-                    $this.map.remove(make_static(item.deref()));
-                    $this.head = ix;
-                    Ok(item)
-                } else {
-                    unreachable!()
-                }
+    // Make sure a slot at `ix` has a generation.
+    #[inline]
+    fn reserve_generation(&mut self, ix: usize) {
+        if self.packing.generation_bits != 0 && ix >= self.generations.len() {
+            self.generations.resize(ix + 1, 0);
+        }
+    }
+
+    // Set the reference count of the slot at `ix`, if counting.
+    #[inline]
+    fn set_refcount(&mut self, ix: usize, count: usize) {
+        if let Some(ref mut refcounts) = self.refcounts {
+            if ix >= refcounts.len() {
+                refcounts.resize(ix + 1, 0);
             }
+            refcounts[ix] = count;
         }
-    } }
-}
+    }
 
-macro_rules! shrink {
-    ($this:ident, $t:ty) => { {
-        let mut remap = <$t>::new();
-        let mut shrunk = Vec::with_capacity($this.count());
-        for (ix, oi) in $this.interned.drain(..).enumerate() {
-            if let Slot::Occupied(i) = oi {
-                let i_static = make_static(i.deref());
-                match (I::from_usize(ix), I::from_usize(shrunk.len())) {
-                    (Some(old_id), Some(new_id)) => {
-                        remap.insert(old_id, new_id);
-                        shrunk.push(Slot::Occupied(i))
-                    }
-                    _ => { $this.map.remove(i_static); }
-                }
+    // Count `count` more references to the occupied slot at `ix`, if counting.
+    #[inline]
+    fn add_refs(&mut self, ix: usize, count: usize) {
+        if let Some(ref mut refcounts) = self.refcounts {
+            // invariant: occupied slots always have a count.
+            refcounts[ix] = refcounts[ix].saturating_add(count);
+        }
+    }
+
+    // Set the hit count of the slot at `ix`, if counting.
+    #[inline]
+    fn set_hits(&mut self, ix: usize, count: usize) {
+        if let Some(ref mut hits) = self.hits {
+            if ix >= hits.len() {
+                hits.resize_with(ix + 1, || AtomicUsize::new(0));
             }
+            *hits[ix].get_mut() = count;
         }
-        $this.interned = shrunk;
-        // invariant: no vacant slots
-        $this.head = !0;
-        $this.map.shrink_to_fit();
-        remap
-    } }
+    }
+
+    // Count `count` more hits of the occupied slot at `ix`, if counting.
+    #[inline]
+    fn add_hits(&self, ix: usize, count: usize) {
+        if let Some(ref hits) = self.hits {
+            // invariant: occupied slots always have a count.
+            hits[ix].fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    // Get the hit count of the occupied slot at `ix`, or zero if not counting.
+    #[inline]
+    fn hits_at(&self, ix: usize) -> usize {
+        self.hits.as_ref().map_or(0, |hits| hits[ix].load(Ordering::Relaxed))
+    }
+
+    // Invalidate all IDs issued for the slot at `ix`.
+    #[inline]
+    fn bump_generation(&mut self, ix: usize) {
+        if let Some(generation) = self.generations.get_mut(ix) {
+            *generation = (*generation + 1) & self.packing.generation_mask();
+        }
+    }
 }
 
 impl<O, I, M> ArenaSet<O, I, M>
@@ -318,7 +496,46 @@ where O: StableDeref,
     pub fn intern<Q>(&mut self, item: Q) -> Result<I, Error>
         where Q: Borrow<O::Target>,
               O: From<Q> {
-        insert!(self, item, |item: Q| { O::from(item) })
+        self.intern_with(item, O::from, |item: &O| item.deref())
+    }
+
+    /// Get the ID of an item without interning it.
+    ///
+    /// Unlike [`intern`], this never modifies the set, so it only needs a
+    /// shared reference, and unknown items don't grow the map.
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.get_id("hello"), Some(0));
+    /// assert_eq!(p.get_id("world"), None);
+    /// assert_eq!(p.count(), 1);
+    /// ```
+    ///
+    /// Complexity: _O([`M::get(K)`])_
+    ///
+    /// [`intern`]: struct.ArenaSet.html#method.intern
+    /// [`M::get(K)`]: trait.Map.html#tymethod.get
+    #[inline]
+    pub fn get_id(&self, item: &O::Target) -> Option<I> {
+        self.map.get(make_static(item)).cloned()
+    }
+
+    /// Check whether an item has been interned.
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert!(p.contains("hello"));
+    /// assert!(!p.contains("world"));
+    /// ```
+    ///
+    /// Complexity: _O([`M::get(K)`])_
+    ///
+    /// [`M::get(K)`]: trait.Map.html#tymethod.get
+    #[inline]
+    pub fn contains(&self, item: &O::Target) -> bool {
+        self.get_id(item).is_some()
     }
 
     /// Disintern an item by its unique ID.
@@ -331,7 +548,7 @@ where O: StableDeref,
     /// assert_eq!(p.intern("hello"), Ok(0));
     /// assert_eq!(p.intern("world"), Ok(1));
     /// assert_eq!(p.disintern(0), Ok("hello".into()));
-    /// assert_eq!(p.resolve::<_, str>(0), Err(shawshank::Error::InvalidId));
+    /// assert_eq!(p.resolve::<_, str>(0), Err(shawshank::Error::InvalidId { index: 0 }));
     /// ```
     ///
     /// Complexity: _O([`M::remove(K)`])_
@@ -340,7 +557,7 @@ where O: StableDeref,
     /// [`shrink`]: struct.ArenaSet.html#method.shrink
     /// [`M::remove(K)`]: trait.Map.html#tymethod.remove
     pub fn disintern<'a, U: Borrow<I>>(&'a mut self, id: U) -> Result<O, Error> {
-        disintern!(self, id)
+        self.disintern_with(id.borrow(), |item: &O| item.deref())
     }
 
     /// Shrink the internal data structures by re-using ID of disinterned items.
@@ -360,15 +577,407 @@ where O: StableDeref,
     /// let remap: BTreeMap<_, _> = p.shrink();
     /// assert_eq!(remap[&1], 0);
     /// assert_eq!(p.resolve(0), Ok("world"));
+    /// assert_eq!(p.get_id("world"), Some(0));
+    /// ```
+    ///
+    /// With [generational IDs], the old IDs of moved items go stale, even
+    /// once their slots are reused:
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut p = shawshank::builder::<String>().generational(8).hash().unwrap();
+    /// let a = p.intern("a").unwrap();
+    /// let b = p.intern("b").unwrap();
+    /// p.disintern(a).unwrap();
+    /// let remap: BTreeMap<_, _> = p.shrink();
+    /// let c = p.intern("c").unwrap();
+    /// assert!(c != b);
+    /// assert!(p.resolve::<_, str>(b).is_err());
+    /// assert_eq!(p.resolve(remap[&b]), Ok("b"));
+    /// assert_eq!(p.resolve(c), Ok("c"));
+    /// ```
+    ///
+    /// This holds even with a single generation bit, as long as each slot is
+    /// reused at most once:
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut p = shawshank::builder::<String>().generational(1).hash().unwrap();
+    /// let a = p.intern("a").unwrap();
+    /// let b = p.intern("b").unwrap();
+    /// p.disintern(a).unwrap();
+    /// let remap: BTreeMap<_, _> = p.shrink();
+    /// assert!(p.resolve::<_, str>(a).is_err());
+    /// assert!(p.resolve::<_, str>(b).is_err());
+    /// assert_eq!(p.resolve(remap[&b]), Ok("b"));
     /// ```
     ///
     /// Complexity: _O(successes * [`T::insert(K)`] + failures * [`M::remove(K)`])_
     ///
+    /// [generational IDs]: struct.ArenaSet.html#generational-ids
     /// [`T::insert(K)`]: trait.Map.html#tymethod.insert
     /// [`M::remove(K)`]: trait.Map.html#tymethod.remove
-    pub fn shrink<T: Map<Key = I, Value = I>>(&mut self) -> T
+    pub fn shrink<T: NewMap<Key = I, Value = I>>(&mut self) -> T
     {
-        shrink!(self, T)
+        self.shrink_with(|item: &O| item.deref())
+    }
+
+    /// Move the items of `other` into this set, and return a map from their
+    /// IDs in `other` to the ones they have here.
+    ///
+    /// Items already interned here keep their existing IDs, and the others
+    /// are moved without copying or reallocating them. Reference counts are
+    /// carried over, and added up for items present in both sets. If there
+    /// isn't room for every item, the ones that don't fit stay in `other`,
+    /// and the map has no entry for them; both sets remain usable.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut p = shawshank::string_arena_set();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// let mut q = shawshank::string_arena_set();
+    /// assert_eq!(q.intern("world"), Ok(0));
+    /// assert_eq!(q.intern("hello"), Ok(1));
+    /// let remap: BTreeMap<_, _> = p.merge(&mut q);
+    /// assert_eq!(remap[&0], 1);
+    /// assert_eq!(remap[&1], 0);
+    /// assert_eq!(p.resolve(1), Ok("world"));
+    /// assert_eq!(q.count(), 0);
+    /// ```
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    /// use shawshank::ArenaSet;
+    ///
+    /// let mut p = ArenaSet::<String>::bounded_with_capacity(1, 0).unwrap();
+    /// let mut q = shawshank::string_arena_set();
+    /// assert_eq!(p.intern("a"), Ok(0));
+    /// assert_eq!(q.intern("b"), Ok(0));
+    /// assert_eq!(q.intern("c"), Ok(1));
+    /// let remap: BTreeMap<_, _> = p.merge(&mut q);
+    /// assert_eq!(remap.len(), 1);
+    /// assert_eq!(p.count(), 2);
+    /// assert_eq!(q.resolve(1), Ok("c"));
+    /// ```
+    ///
+    /// Complexity: _O(n * ([`M::get(K)`] + [`M::insert(K, V)`]))_ for `n` slots in `other`
+    ///
+    /// [`M::get(K)`]: trait.Map.html#tymethod.get
+    /// [`M::insert(K, V)`]: trait.Map.html#tymethod.insert
+    pub fn merge<T, N>(&mut self, other: &mut ArenaSet<O, I, N>) -> T
+        where T: NewMap<Key = I, Value = I>,
+              N: Map<Key = &'static O::Target, Value = I> {
+        self.merge_with(other, |item: &O| item.deref())
+    }
+}
+
+// The logic shared by `ArenaSet` and `StadiumSet`, which only differ in `key`,
+// the projection of an item to the key under which it's mapped.
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Value = I>
+{
+    fn intern_with<K, Q, F, G>(&mut self, item: Q, to_owned: F, key: G) -> Result<I, Error>
+        where K: ? Sized + 'static,
+              M: Map<Key = &'static K>,
+              Q: Borrow<K>,
+              F: FnOnce(Q) -> O,
+              G: Fn(&O) -> &K {
+        // fast case: item already interned
+        if let Some(&id) = self.map.get(make_static(item.borrow())) {
+            if self.refcounts.is_some() || self.hits.is_some() {
+                let ix = self.index_of(&id)?;
+                self.add_refs(ix, 1);
+                self.add_hits(ix, 1);
+            }
+            return Ok(id);
+        }
+        let owned = to_owned(item);
+        let (ix, id) = self.next_slot(&owned)?;
+        self.occupy(ix, id, owned, key);
+        self.add_hits(ix, 1);
+        Ok(id)
+    }
+
+    // Find the slot `item` will be placed in, and the ID it will get, without
+    // changing anything.
+    fn next_slot(&self, item: &O) -> Result<(usize, I), Error> {
+        // don't let IDs overflow
+        let cnt = self.count();
+        if cnt != 0 && cnt - 1 == self.max_idx {
+            return Err(Error::IdOverflow { max_idx: self.max_idx });
+        }
+        let ix = match self.sorted {
+            Some(ref sorted) => self.sorted_slot(sorted, item)?,
+            // invariant: if `self.head != !0`, then it has an index to vacant
+            // slot; otherwise there are none.
+            None if self.head == !0 => self.interned.len(),
+            None => self.head,
+        };
+        Ok((ix, self.to_id(ix)?))
+    }
+
+    // Place an item in the slot found by `next_slot`.
+    fn occupy<K, G>(&mut self, ix: usize, id: I, owned: O, key: G)
+        where K: ? Sized + 'static,
+              M: Map<Key = &'static K>,
+              G: Fn(&O) -> &K {
+        let reference = make_static(key(&owned));
+        if ix >= self.interned.len() {
+            // only sorted sets skip ahead, and they don't link vacant slots
+            self.interned.resize_with(ix, || Slot::Vacant(!0));
+            self.interned.push(Slot::Occupied(owned));
+            self.reserve_generation(ix);
+        } else if let Slot::Vacant(next) = mem::replace(&mut self.interned[ix], Slot::Occupied(owned)) {
+            self.head = next;
+        } else {
+            unreachable!()
+        }
+        self.map.insert(reference, id);
+        self.set_refcount(ix, 1);
+        self.set_hits(ix, 0);
+    }
+
+    // Move every item out of `other`, unless it is already interned or there
+    // is no room for it, and return a map from its old IDs to the new ones.
+    fn merge_with<T, K, N, G>(&mut self, other: &mut ArenaSet<O, I, N>, key: G) -> T
+        where T: NewMap<Key = I, Value = I>,
+              K: ? Sized + 'static,
+              M: Map<Key = &'static K>,
+              N: Map<Key = &'static K, Value = I>,
+              G: Fn(&O) -> &K {
+        let mut remap = T::new();
+        for old_ix in 0..other.interned.len() {
+            let (ix, id, existing) = match other.interned[old_ix] {
+                Slot::Occupied(ref item) => match self.map.get(make_static(key(item))).cloned() {
+                    Some(id) => match self.index_of(&id) {
+                        Ok(ix) => (ix, id, true),
+                        Err(_) => continue,
+                    },
+                    // leave the item in `other` if it doesn't fit
+                    None => match self.next_slot(item) {
+                        Ok((ix, id)) => (ix, id, false),
+                        Err(_) => continue,
+                    },
+                },
+                Slot::Vacant(_) => continue,
+            };
+            let old_id = match other.to_id(old_ix) {
+                Ok(old_id) => old_id,
+                Err(_) => continue,
+            };
+            let count = other.refcounts.as_ref().map_or(1, |refcounts| refcounts[old_ix]);
+            let hits = other.hits_at(old_ix);
+            let item = match other.disintern_with(&old_id, &key) {
+                Ok(item) => item,
+                Err(_) => continue,
+            };
+            if existing {
+                self.add_refs(ix, count);
+            } else {
+                self.occupy(ix, id, item, &key);
+                self.set_refcount(ix, count);
+            }
+            self.add_hits(ix, hits);
+            remap.insert(old_id, id);
+        }
+        remap
+    }
+
+    fn disintern_with<K, G>(&mut self, id: &I, key: G) -> Result<O, Error>
+        where K: ? Sized + 'static,
+              M: Map<Key = &'static K>,
+              G: Fn(&O) -> &K {
+        let ix = self.index_of(id)?;
+        // invariant: `index_of` only returns indices of occupied slots; and
+        // `self.head` has been updated correctly. Sorted sets don't link
+        // vacant slots, so their `self.head` is always `!0`.
+        if let Slot::Occupied(item) = mem::replace(&mut self.interned[ix], Slot::Vacant(self.head)) {
+            self.map.remove(make_static(key(&item)));
+            if self.sorted.is_none() {
+                self.head = ix;
+            }
+            self.bump_generation(ix);
+            self.set_refcount(ix, 0);
+            self.set_hits(ix, 0);
+            Ok(item)
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn shrink_with<T, K, G>(&mut self, key: G) -> T
+        where T: NewMap<Key = I, Value = I>,
+              K: ? Sized + 'static,
+              M: Map<Key = &'static K>,
+              G: Fn(&O) -> &K {
+        let mut remap = T::new();
+        let mut shrunk = Vec::with_capacity(self.count());
+        let interned = mem::take(&mut self.interned);
+        // generations are never reset, and a slot is bumped exactly when its
+        // item leaves it, as in `disintern`. A slot an item moves into is
+        // either vacant, and was bumped when it became so, or was just
+        // vacated here; bumping it again could bring a stale ID back to life.
+        for (ix, oi) in interned.into_iter().enumerate() {
+            if let Slot::Occupied(i) = oi {
+                let i_static = make_static(key(&i));
+                let old_id = self.to_id(ix);
+                let new_id = if ix == shrunk.len() {
+                    old_id
+                } else {
+                    // slots only move down, so the one moved into has
+                    // already been visited
+                    self.bump_generation(ix);
+                    self.to_id(shrunk.len())
+                };
+                match (old_id, new_id) {
+                    (Ok(old_id), Ok(new_id)) => {
+                        self.map.insert(i_static, new_id);
+                        remap.insert(old_id, new_id);
+                        shrunk.push(Slot::Occupied(i));
+                        // counts only ever move to lower indices
+                        if let Some(ref mut refcounts) = self.refcounts {
+                            refcounts[shrunk.len() - 1] = refcounts[ix];
+                        }
+                        let hits = self.hits_at(ix);
+                        self.set_hits(shrunk.len() - 1, hits);
+                    }
+                    _ => {
+                        // the item is dropped, so its ID must go stale
+                        if ix == shrunk.len() {
+                            self.bump_generation(ix);
+                        }
+                        self.map.remove(i_static);
+                    }
+                }
+            }
+        }
+        self.interned = shrunk;
+        if let Some(ref mut refcounts) = self.refcounts {
+            refcounts.truncate(self.interned.len());
+        }
+        if let Some(ref mut hits) = self.hits {
+            hits.truncate(self.interned.len());
+        }
+        // invariant: no vacant slots
+        self.head = !0;
+        self.map.shrink_to_fit();
+        remap
+    }
+
+    // Move the items to new slots, in the order `sort` puts them in, with
+    // item `k` at `(k + 1) * spacing - 1`, or closer if that would pass
+    // `self.max_idx`. Returns a map from the old IDs to the new ones; an item
+    // whose ID can't be converted is disinterned, as in `shrink_with`.
+    fn renumber_with<T, K, G, F>(&mut self, key: G, spacing: usize, sort: F) -> T
+        where T: NewMap<Key = I, Value = I>,
+              K: ? Sized + 'static,
+              M: Map<Key = &'static K>,
+              G: Fn(&O) -> &K,
+              F: FnOnce(&mut [(usize, I, O)]) {
+        let mut remap = T::new();
+        let interned = mem::take(&mut self.interned);
+        let refcounts = self.refcounts.take();
+        let hits = self.hits.take();
+        // the old IDs must be found before any generation changes
+        let mut items = Vec::with_capacity(self.count());
+        for (ix, slot) in interned.into_iter().enumerate() {
+            if let Slot::Occupied(item) = slot {
+                match self.to_id(ix) {
+                    Ok(old_id) => items.push((ix, old_id, item)),
+                    Err(_) => { self.map.remove(make_static(key(&item))); }
+                }
+            }
+        }
+        sort(&mut items);
+        let spacing = cmp::min(spacing, cmp::max(1, self.max_idx / cmp::max(1, items.len())));
+        let new_len = items.len() * spacing;
+        // generations are never reset, and a slot is bumped exactly when its
+        // item leaves it, as in `shrink_with`. A slot an item moves into was
+        // either vacant, and bumped when it became so, or is vacated here.
+        if new_len != 0 {
+            self.reserve_generation(new_len - 1);
+        }
+        for (k, &(ix, _, _)) in items.iter().enumerate() {
+            if (k + 1) * spacing - 1 != ix {
+                self.bump_generation(ix);
+            }
+        }
+        self.interned.resize_with(new_len, || Slot::Vacant(!0));
+        self.refcounts = refcounts.as_ref().map(|_| vec![0; new_len]);
+        self.hits = hits.as_ref().map(|_| (0..new_len).map(|_| AtomicUsize::new(0)).collect());
+        for (k, (ix, old_id, item)) in items.into_iter().enumerate() {
+            let new_ix = (k + 1) * spacing - 1;
+            let reference = make_static(key(&item));
+            match self.to_id(new_ix) {
+                Ok(new_id) => {
+                    self.map.insert(reference, new_id);
+                    remap.insert(old_id, new_id);
+                    self.interned[new_ix] = Slot::Occupied(item);
+                    if let Some(ref refcounts) = refcounts {
+                        self.set_refcount(new_ix, refcounts[ix]);
+                    }
+                    if let Some(ref hits) = hits {
+                        self.set_hits(new_ix, hits[ix].load(Ordering::Relaxed));
+                    }
+                }
+                Err(_) => { self.map.remove(reference); }
+            }
+        }
+        // sorted sets don't link vacant slots
+        self.head = !0;
+        if self.sorted.is_none() {
+            self.relink_vacant();
+        }
+        remap
+    }
+
+    // Rebuild a set from its parts, with `key` projecting each item to the key
+    // under which it's mapped. Untrusted parts are validated.
+    fn from_parts<K, F>(parts: Parts<O>, key: F) -> Result<Self, &'static str>
+        where K: ? Sized + 'static,
+              M: NewMap<Key = &'static K>,
+              F: Fn(&O) -> &K {
+        let mut p = Self::generational_with_capacity(parts.generation_bits, parts.slots.len())
+            .map_err(|_| "generation bits out of range")?;
+        if parts.max_idx > p.max_idx {
+            return Err("maximum index out of range");
+        }
+        p.max_idx = parts.max_idx;
+        if parts.slots.len() > p.max_idx.saturating_add(1) {
+            return Err("more slots than the maximum index allows");
+        }
+        if parts.generation_bits == 0 && !parts.generations.is_empty() {
+            return Err("generations without generation bits");
+        }
+        if parts.generation_bits != 0 {
+            if parts.generations.len() < parts.slots.len() {
+                return Err("missing generations");
+            }
+            if parts.generations.iter().any(|&g| g > p.packing.generation_mask()) {
+                return Err("generation out of range");
+            }
+        }
+        p.generations = parts.generations;
+        for (ix, slot) in parts.slots.into_iter().enumerate() {
+            match slot {
+                Some(item) => {
+                    let id = p.to_id(ix).map_err(|_| "could not convert index to ID")?;
+                    let reference = make_static(key(&item));
+                    p.interned.push(Slot::Occupied(item));
+                    if p.map.insert(reference, id).is_some() {
+                        return Err("duplicate item");
+                    }
+                }
+                None => p.interned.push(Slot::Vacant(!0)),
+            }
+        }
+        p.relink_vacant();
+        Ok(p)
     }
 }
 
@@ -405,8 +1014,7 @@ where O: StableDeref<Target = R>,
         where Q: Borrow<< O::Target as Deref >::Target>,
               O::Target: From<Q>,
               O: From<< O as Deref >::Target> {
-        let ref mut this = self.0;
-        insert!(this, item, |item: Q| { O::from(O::Target::from(item)) })
+        self.0.intern_with(item, |item: Q| O::from(O::Target::from(item)), |item: &O| item.deref().deref())
     }
 
     /// Analogue of [`disintern`].
@@ -423,8 +1031,7 @@ where O: StableDeref<Target = R>,
     ///
     /// [`disintern`]: struct.ArenaSet.html#method.disintern
     pub fn disintern<'a, U: Borrow<I>>(&'a mut self, id: U) -> Result<O, Error> {
-        let ref mut this = self.0;
-        disintern!(this, id)
+        self.0.disintern_with(id.borrow(), |item: &O| item.deref().deref())
     }
 
     /// Analogue of [`resolve`].
@@ -447,17 +1054,153 @@ where O: StableDeref<Target = R>,
         self.0.resolve(id)
     }
 
+    /// Analogue of [`get_id`].
+    ///
+    /// ```
+    /// let mut p = shawshank::byte_stadium_set();
+    /// assert_eq!(p.intern(&[1,2,3][..]), Ok(0));
+    /// assert_eq!(p.get_id(&[1,2,3]), Some(0));
+    /// assert_eq!(p.get_id(&[4,5,6]), None);
+    /// ```
+    ///
+    /// [`get_id`]: struct.ArenaSet.html#method.get_id
+    #[inline]
+    pub fn get_id(&self, item: &< R as Deref >::Target) -> Option<I> {
+        self.0.map.get(make_static(item)).cloned()
+    }
+
+    /// Analogue of [`contains`].
+    ///
+    /// ```
+    /// let mut p = shawshank::string_stadium_set();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert!(p.contains("hello"));
+    /// assert!(!p.contains("world"));
+    /// ```
+    ///
+    /// [`contains`]: struct.ArenaSet.html#method.contains
+    #[inline]
+    pub fn contains(&self, item: &< R as Deref >::Target) -> bool {
+        self.get_id(item).is_some()
+    }
+
     /// Analogue of [`shrink`].
     ///
     /// [`shrink`]: struct.ArenaSet.html#method.shrink
-    pub fn shrink<T: Map<Key = I, Value = I>>(&mut self) -> T
+    pub fn shrink<T: NewMap<Key = I, Value = I>>(&mut self) -> T
     {
-        let ref mut this = self.0;
-        shrink!(this, T)
+        self.0.shrink_with(|item: &O| item.deref().deref())
+    }
+
+    /// Analogue of [`merge`].
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// let mut p = shawshank::byte_stadium_set();
+    /// let mut q = shawshank::byte_stadium_set();
+    /// assert_eq!(p.intern(vec![1, 2]), Ok(0));
+    /// assert_eq!(q.intern(vec![3]), Ok(0));
+    /// assert_eq!(q.intern(vec![1, 2]), Ok(1));
+    /// let remap: HashMap<_, _> = p.merge(&mut q);
+    /// assert_eq!(remap[&0], 1);
+    /// assert_eq!(remap[&1], 0);
+    /// assert_eq!(p.resolve::<_, Vec<u8>>(1), Ok(&vec![3]));
+    /// ```
+    ///
+    /// [`merge`]: struct.ArenaSet.html#method.merge
+    pub fn merge<T, N>(&mut self, other: &mut StadiumSet<O, R, I, N>) -> T
+        where T: NewMap<Key = I, Value = I>,
+              N: Map<Key = &'static < R as Deref >::Target, Value = I> {
+        self.0.merge_with(&mut other.0, |item: &O| item.deref().deref())
+    }
+}
+
+impl<O, I, M> Interner for ArenaSet<O, I, M>
+where O: StableDeref,
+      O::Target: 'static,
+      I: Copy + ToPrimitive + FromPrimitive + Bounded,
+      M: Map<Key = &'static O::Target, Value = I>
+{
+    type Item = O::Target;
+    type Input = O;
+    type Owned = O;
+    type Id = I;
+
+    fn intern<Q>(&mut self, item: Q) -> Result<I, Error>
+        where Q: Borrow<O::Target>,
+              O: From<Q> {
+        ArenaSet::intern(self, item)
+    }
+
+    fn resolve<U: Borrow<I>>(&self, id: U) -> Result<&O::Target, Error> {
+        let ix = self.index_of(id.borrow())?;
+        self.add_hits(ix, 1);
+        match self.interned[ix] {
+            Slot::Occupied(ref item) => Ok(item.deref()),
+            // invariant: `index_of` only returns indices of occupied slots.
+            Slot::Vacant(_) => unreachable!(),
+        }
+    }
+
+    fn disintern<U: Borrow<I>>(&mut self, id: U) -> Result<O, Error> {
+        ArenaSet::disintern(self, id)
+    }
+
+    fn shrink<T: NewMap<Key = I, Value = I>>(&mut self) -> T {
+        ArenaSet::shrink(self)
+    }
+
+    fn count(&self) -> usize {
+        ArenaSet::count(self)
+    }
+}
+
+impl<O, R, I, M> Interner for StadiumSet<O, R, I, M>
+where O: StableDeref<Target = R> + From<R>,
+      R: 'static + StableDeref,
+      I: Copy + ToPrimitive + FromPrimitive + Bounded,
+      M: Map<Key = &'static < R as Deref >::Target, Value = I>
+{
+    type Item = R::Target;
+    type Input = R;
+    type Owned = O;
+    type Id = I;
+
+    fn intern<Q>(&mut self, item: Q) -> Result<I, Error>
+        where Q: Borrow<R::Target>,
+              R: From<Q> {
+        StadiumSet::intern(self, item)
+    }
+
+    fn resolve<U: Borrow<I>>(&self, id: U) -> Result<&R::Target, Error> {
+        self.0.resolve::<_, O>(id).map(|item| item.deref().deref())
+    }
+
+    fn disintern<U: Borrow<I>>(&mut self, id: U) -> Result<O, Error> {
+        StadiumSet::disintern(self, id)
+    }
+
+    fn shrink<T: NewMap<Key = I, Value = I>>(&mut self) -> T {
+        StadiumSet::shrink(self)
+    }
+
+    fn count(&self) -> usize {
+        self.0.count()
     }
 }
 
 /// Errors that may occur when using an [`ArenaSet`].
+///
+/// ```
+/// use shawshank::Error;
+///
+/// let p = shawshank::string_arena_set();
+/// let err = p.resolve::<_, str>(7).unwrap_err();
+/// assert_eq!(err, Error::InvalidId { index: 7 });
+/// assert_eq!(err.to_string(), "no item is interned at index 7");
+/// ```
+///
 /// [`ArenaSet`]: struct.ArenaSet.html
 #[derive(Eq, PartialEq, Clone, Copy, Debug)]
 pub enum Error {
@@ -467,7 +1210,10 @@ pub enum Error {
     ///
     /// [`intern`]: struct.ArenaSet.html#method.intern
     /// [`disintern`]: struct.ArenaSet.html#method.disintern
-    InvalidId,
+    InvalidId {
+        /// The index the ID refers to.
+        index: usize,
+    },
 
     /// Could not convert an ID type to a `Vec` index.
     FromIdFailed,
@@ -482,9 +1228,94 @@ pub enum Error {
     ///
     /// [`ArenaSet`]: struct.ArenaSet.html
     /// [`intern`]: struct.ArenaSet.html#method.intern
-    IdOverflow,
+    IdOverflow {
+        /// The maximum index that was configured, or that the ID type allows.
+        max_idx: usize,
+    },
+
+    /// The ID refers to a slot whose item has since been disinterned. Only
+    /// reported by sets with [generational IDs].
+    ///
+    /// [generational IDs]: struct.ArenaSet.html#generational-ids
+    StaleId {
+        /// The index of the slot the ID refers to.
+        index: usize,
+    },
+
+    /// A [snapshot] is truncated, fails its checksum, or is otherwise
+    /// inconsistent. Also reported for invalid files given to a
+    /// `MappedArenaSet`.
+    ///
+    /// [snapshot]: struct.ArenaSet.html#snapshots
+    CorruptSnapshot,
+
+    /// An I/O error occurred while reading a [snapshot].
+    ///
+    /// [snapshot]: struct.ArenaSet.html#snapshots
+    Io(io::ErrorKind),
+
+    /// The set isn't configured for what was asked of it, e.g. a [`Symbol`]
+    /// in a set that isn't [refcounted] and generational, symbols declared by
+    /// `intern_symbols!` that can't get their constant IDs, or [`Builder`]
+    /// options the set being built doesn't support. Holds a description of
+    /// the problem.
+    ///
+    /// [`Symbol`]: struct.Symbol.html
+    /// [`Builder`]: struct.Builder.html
+    /// [refcounted]: struct.ArenaSet.html#method.refcounted
+    Unsupported(&'static str),
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidId { index } => write!(f, "no item is interned at index {}", index),
+            Error::FromIdFailed => f.write_str("could not convert an ID to an index"),
+            Error::ToIdFailed => f.write_str("could not convert an index to an ID"),
+            Error::IdOverflow { max_idx } => {
+                write!(f, "no more unique IDs are available (maximum index is {})", max_idx)
+            }
+            Error::StaleId { index } => {
+                write!(f, "the item at index {} was disinterned after the ID was issued", index)
+            }
+            Error::CorruptSnapshot => f.write_str("the snapshot is corrupt"),
+            Error::Io(kind) => write!(f, "I/O error while reading a snapshot: {}", kind),
+            Error::Unsupported(what) => write!(f, "unsupported: {}", what),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => Error::CorruptSnapshot,
+            kind => Error::Io(kind),
+        }
+    }
+}
+
+// Everything needed to rebuild an `ArenaSet` with the same IDs. The map and
+// free list are derived from the slots.
+struct Parts<O> {
+    max_idx: usize,
+    generation_bits: u32,
+    generations: Vec<usize>,
+    slots: Vec<Option<O>>,
+}
+
+// How a sorted set keeps IDs in the order of their items: `neighbors` finds
+// the IDs of the items just before and after a new one, which is then placed
+// halfway between them, or `spacing` past the last item. It's a function
+// pointer so that only `ArenaSet::sorted` needs an `OrderedMap`.
+struct Sorted<O, I, M> {
+    spacing: usize,
+    neighbors: Neighbors<O, I, M>,
+}
+
+type Neighbors<O, I, M> = fn(&M, &O) -> (Option<I>, Option<I>);
+
 // Aside: it'd be really cool if the Rust compiler could figure out that
 // `Slot<String>` can be represented by 24 instead of 32 bytes on x86-64.
 // Because the heap pointer in `String` is `NonZero`, that can be used as
@@ -495,7 +1326,99 @@ enum Slot<T> {
     Occupied(T),
 }
 
-// trust us, we're engineers
-fn make_static<T: ? Sized>(t: &T) -> &'static T {
+// How slot indices and their generations are packed into IDs. The generation
+// occupies the `generation_bits` bits above the `index_bits` of the index, and
+// the result is offset by the minimum value of the ID type.
+#[derive(Clone, Copy)]
+struct Packing {
+    offset: usize,
+    generation_bits: u32,
+    index_bits: u32,
+}
+
+impl Packing {
+    fn plain<I: Bounded + ToPrimitive>() -> Result<Packing, Error> {
+        Ok(Packing {
+            offset: I::min_value().to_usize().ok_or(Error::FromIdFailed)?,
+            generation_bits: 0,
+            index_bits: usize::BITS,
+        })
+    }
+
+    fn generational<I: Bounded + ToPrimitive>(generation_bits: u32) -> Result<Packing, Error> {
+        let mut packing = Packing::plain::<I>()?;
+        if generation_bits == 0 {
+            return Ok(packing);
+        }
+        // only use as many bits as every ID in the range can represent
+        let max_possible = packing.max_idx::<I>()?;
+        let id_bits = match max_possible.checked_add(1) {
+            Some(n) => usize::BITS - 1 - n.leading_zeros(),
+            None => usize::BITS,
+        };
+        if generation_bits >= id_bits {
+            return Err(Error::IdOverflow { max_idx: max_possible });
+        }
+        packing.generation_bits = generation_bits;
+        packing.index_bits = id_bits - generation_bits;
+        Ok(packing)
+    }
+
+    #[inline]
+    fn generation_mask(&self) -> usize {
+        (1 << self.generation_bits) - 1
+    }
+
+    #[inline]
+    fn pack(&self, ix: usize, generations: &[usize]) -> usize {
+        self.pack_one(ix, generations.get(ix).cloned().unwrap_or(0))
+    }
+
+    #[inline]
+    fn pack_one(&self, ix: usize, generation: usize) -> usize {
+        if self.generation_bits == 0 {
+            self.offset + ix
+        } else {
+            self.offset + ((generation << self.index_bits) | ix)
+        }
+    }
+
+    // Fails if the ID is below the minimum value of the ID type.
+    #[inline]
+    fn unpack(&self, raw: usize) -> Result<(usize, usize), Error> {
+        let raw = raw.checked_sub(self.offset).ok_or(Error::FromIdFailed)?;
+        if self.generation_bits == 0 {
+            Ok((raw, 0))
+        } else {
+            Ok((raw & ((1 << self.index_bits) - 1), raw >> self.index_bits))
+        }
+    }
+
+    // Find the index of the occupied slot that an ID refers to.
+    #[inline]
+    fn locate<T>(&self, raw: usize, slots: &[Slot<T>], generations: &[usize]) -> Result<usize, Error> {
+        let (ix, generation) = self.unpack(raw)?;
+        match (slots.get(ix), generations.get(ix)) {
+            (_, Some(&current)) if current != generation => Err(Error::StaleId { index: ix }),
+            (Some(&Slot::Occupied(_)), _) => Ok(ix),
+            _ => Err(Error::InvalidId { index: ix }),
+        }
+    }
+
+    // The widest maximum index for IDs packed this way.
+    fn max_idx<I: Bounded + ToPrimitive>(&self) -> Result<usize, Error> {
+        if self.generation_bits == 0 {
+            Ok(I::max_value().to_usize().ok_or(Error::FromIdFailed)? - self.offset)
+        } else {
+            Ok((1 << self.index_bits) - 1)
+        }
+    }
+}
+
+// Pretend that a reference to an item lives forever, so that it can be used
+// as a key in a map. This is only sound because implementations of the unsafe
+// `Map` trait promise not to use a key once its pair is removed, and every set
+// drops its map before its items.
+pub(crate) fn make_static<T: ? Sized>(t: &T) -> &'static T {
     unsafe { &*(t as *const T) }
 }
@@ -0,0 +1,255 @@
+use std::borrow::Borrow;
+use std::iter::FromIterator;
+use std::ops::Deref;
+use std::sync::atomic::Ordering;
+
+use num_traits::{Bounded, ToPrimitive, FromPrimitive};
+use stable_deref_trait::StableDeref;
+
+use traits::{Map, NewMap};
+
+use super::{ArenaSet, StadiumSet, Error};
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      O::Target: 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Key = &'static O::Target, Value = I>
+{
+    /// Intern several items, receiving their IDs in the same order.
+    ///
+    /// Room for the items is reserved up front. If any of them can't be
+    /// interned, e.g. with [`Error::IdOverflow`], the whole batch is rolled
+    /// back: the items it added are disinterned again, and reference and hit
+    /// counts are restored.
+    ///
+    /// ```
+    /// use shawshank::ArenaSet;
+    ///
+    /// let mut p = shawshank::string_arena_set();
+    /// assert_eq!(p.intern_all(vec!["a", "b", "a"]), Ok(vec![0, 1, 0]));
+    ///
+    /// let mut p = ArenaSet::<String>::bounded_with_capacity(1, 0).unwrap();
+    /// assert_eq!(p.intern("a"), Ok(0));
+    /// assert_eq!(p.intern_all(vec!["a", "b", "c"]), Err(shawshank::Error::IdOverflow { max_idx: 1 }));
+    /// assert_eq!(p.count(), 1);
+    /// ```
+    ///
+    /// [`Error::IdOverflow`]: enum.Error.html#variant.IdOverflow
+    pub fn intern_all<It, Q>(&mut self, items: It) -> Result<Vec<I>, Error>
+        where It: IntoIterator<Item = Q>,
+              Q: Borrow<O::Target>,
+              O: From<Q> {
+        self.intern_all_with(items, O::from, |item: &O| item.deref())
+    }
+}
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      I: Bounded + ToPrimitive + FromPrimitive,
+      M: Map
+{
+    /// Resolve several items by their unique IDs, in the same order.
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set();
+    /// let ids = p.intern_all(vec!["hello", "world"]).unwrap();
+    /// assert_eq!(p.resolve_all(&ids), Ok(vec!["hello", "world"]));
+    /// ```
+    pub fn resolve_all<'a, Q: ? Sized>(&'a self, ids: &[I]) -> Result<Vec<&'a Q>, Error>
+        where O: Borrow<Q> {
+        ids.iter().map(|id| self.resolve(id)).collect()
+    }
+}
+
+// The logic shared by `ArenaSet` and `StadiumSet`, as in `arena_set.rs`.
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Value = I>
+{
+    fn intern_all_with<It, K, Q, F, G>(&mut self, items: It, to_owned: F, key: G) -> Result<Vec<I>, Error>
+        where It: IntoIterator<Item = Q>,
+              K: ? Sized + 'static,
+              M: Map<Key = &'static K>,
+              Q: Borrow<K>,
+              F: Fn(Q) -> O,
+              G: Fn(&O) -> &K {
+        let items = items.into_iter();
+        let (additional, _) = items.size_hint();
+        self.interned.reserve(additional);
+        self.map.reserve(additional);
+        // each ID, and whether its item was added by this call
+        let mut interned = Vec::with_capacity(additional);
+        for item in items {
+            let count = self.count();
+            match self.intern_with(item, &to_owned, &key) {
+                Ok(id) => interned.push((id, self.count() != count)),
+                Err(err) => {
+                    // undo everything, latest first, so that an item added
+                    // and then interned again ends up fully disinterned
+                    for (id, added) in interned.into_iter().rev() {
+                        if added {
+                            let _ = self.disintern_with(&id, &key);
+                        } else if let Ok(ix) = self.index_of(&id) {
+                            if let Some(ref mut refcounts) = self.refcounts {
+                                refcounts[ix] -= 1;
+                            }
+                            if let Some(ref hits) = self.hits {
+                                hits[ix].fetch_sub(1, Ordering::Relaxed);
+                            }
+                        }
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(interned.into_iter().map(|(id, _)| id).collect())
+    }
+}
+
+/// Interns every item.
+///
+/// # Panics
+///
+/// Panics if an item can't be interned, e.g. with [`Error::IdOverflow`].
+/// The items before it stay interned.
+///
+/// ```
+/// let mut p = shawshank::string_arena_set();
+/// p.extend(vec!["a", "b", "a"]);
+/// assert_eq!(p.count(), 2);
+/// ```
+///
+/// [`Error::IdOverflow`]: enum.Error.html#variant.IdOverflow
+impl<O, I, M, Q> Extend<Q> for ArenaSet<O, I, M>
+where O: StableDeref + From<Q>,
+      O::Target: 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Key = &'static O::Target, Value = I>,
+      Q: Borrow<O::Target>
+{
+    fn extend<It: IntoIterator<Item = Q>>(&mut self, items: It) {
+        let items = items.into_iter();
+        let (additional, _) = items.size_hint();
+        self.interned.reserve(additional);
+        self.map.reserve(additional);
+        for item in items {
+            if let Err(err) = self.intern(item) {
+                panic!("could not intern an item: {}", err);
+            }
+        }
+    }
+}
+
+/// Creates a set of every item, as with [`Extend`].
+///
+/// # Panics
+///
+/// Panics if the set can't be created, or an item can't be interned.
+///
+/// ```
+/// use shawshank::ArenaSet;
+///
+/// let p: ArenaSet<String> = vec!["a", "b", "a"].into_iter().collect();
+/// assert_eq!(p.get_id("b"), Some(1));
+/// ```
+///
+/// [`Extend`]: #impl-Extend%3CQ%3E
+impl<O, I, M, Q> FromIterator<Q> for ArenaSet<O, I, M>
+where O: StableDeref + From<Q>,
+      O::Target: 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: NewMap<Key = &'static O::Target, Value = I>,
+      Q: Borrow<O::Target>
+{
+    fn from_iter<It: IntoIterator<Item = Q>>(items: It) -> Self {
+        let mut p = match ArenaSet::new() {
+            Ok(p) => p,
+            Err(err) => panic!("could not create an ArenaSet: {}", err),
+        };
+        p.extend(items);
+        p
+    }
+}
+
+impl<O, R, I, M> StadiumSet<O, R, I, M>
+where O: StableDeref<Target = R> + From<R>,
+      R: 'static + StableDeref,
+      I: Copy + ToPrimitive + FromPrimitive + Bounded,
+      M: Map<Key = &'static < R as Deref >::Target, Value = I>
+{
+    /// Analogue of [`intern_all`].
+    ///
+    /// ```
+    /// let mut p = shawshank::byte_stadium_set();
+    /// assert_eq!(p.intern_all(vec![vec![1], vec![2], vec![1]]), Ok(vec![0, 1, 0]));
+    /// ```
+    ///
+    /// [`intern_all`]: struct.ArenaSet.html#method.intern_all
+    pub fn intern_all<It, Q>(&mut self, items: It) -> Result<Vec<I>, Error>
+        where It: IntoIterator<Item = Q>,
+              Q: Borrow<R::Target>,
+              R: From<Q> {
+        self.0.intern_all_with(items, |item: Q| O::from(R::from(item)), |item: &O| item.deref().deref())
+    }
+
+    /// Analogue of [`resolve_all`].
+    ///
+    /// [`resolve_all`]: struct.ArenaSet.html#method.resolve_all
+    pub fn resolve_all<'a, Q: ? Sized>(&'a self, ids: &[I]) -> Result<Vec<&'a Q>, Error>
+        where O: Borrow<Q> {
+        self.0.resolve_all(ids)
+    }
+}
+
+/// Analogue of the `Extend` impl for [`ArenaSet`].
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+impl<O, R, I, M, Q> Extend<Q> for StadiumSet<O, R, I, M>
+where O: StableDeref<Target = R> + From<R>,
+      R: 'static + StableDeref + From<Q>,
+      I: Copy + ToPrimitive + FromPrimitive + Bounded,
+      M: Map<Key = &'static < R as Deref >::Target, Value = I>,
+      Q: Borrow<R::Target>
+{
+    fn extend<It: IntoIterator<Item = Q>>(&mut self, items: It) {
+        let items = items.into_iter();
+        let (additional, _) = items.size_hint();
+        self.0.interned.reserve(additional);
+        self.0.map.reserve(additional);
+        for item in items {
+            if let Err(err) = self.intern(item) {
+                panic!("could not intern an item: {}", err);
+            }
+        }
+    }
+}
+
+/// Analogue of the `FromIterator` impl for [`ArenaSet`].
+///
+/// ```
+/// use std::sync::Arc;
+/// use shawshank::StadiumSet;
+///
+/// let p: StadiumSet<Arc<Vec<u8>>> = vec![vec![1], vec![2]].into_iter().collect();
+/// assert_eq!(p.get_id(&[2]), Some(1));
+/// ```
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+impl<O, R, I, M, Q> FromIterator<Q> for StadiumSet<O, R, I, M>
+where O: StableDeref<Target = R> + From<R>,
+      R: 'static + StableDeref + From<Q>,
+      I: Copy + ToPrimitive + FromPrimitive + Bounded,
+      M: NewMap<Key = &'static < R as Deref >::Target, Value = I>,
+      Q: Borrow<R::Target>
+{
+    fn from_iter<It: IntoIterator<Item = Q>>(items: It) -> Self {
+        let mut p = match ArenaSet::new() {
+            Ok(p) => StadiumSet(p),
+            Err(err) => panic!("could not create a StadiumSet: {}", err),
+        };
+        p.extend(items);
+        p
+    }
+}
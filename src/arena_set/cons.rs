@@ -0,0 +1,166 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::slice;
+
+use num_traits::{Bounded, ToPrimitive, FromPrimitive};
+
+use super::{Error, Packing};
+use super::hash_index::{HashIndex, slot_of};
+
+/// A hash-consing table: an internment structure for plain values, which are
+/// stored inline rather than behind a [`StableDeref`] owner.
+///
+/// Since the slot vector may move its values when it grows, nothing can
+/// borrow from it as [`ArenaSet`]'s map does. Instead, as in
+/// [`HashedArenaSet`], a 64-bit hash is kept next to each value, and the
+/// index holds nothing but IDs, so each value is stored exactly once. This
+/// suits small values, such as the nodes of an expression DAG that refer to
+/// their children by ID, so that structurally equal nodes collapse into one.
+///
+/// ```
+/// #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+/// enum Expr {
+///     Lit(i64),
+///     Add(usize, usize),
+/// }
+///
+/// let mut t = shawshank::builder::<Expr>().cons_hash().unwrap();
+/// let one = t.intern(Expr::Lit(1)).unwrap();
+/// let two = t.intern(Expr::Add(one, one)).unwrap();
+/// let also_one = t.intern(Expr::Lit(1)).unwrap();
+/// assert_eq!(t.intern(Expr::Add(also_one, one)), Ok(two));
+/// assert_eq!(t.resolve(two), Ok(&Expr::Add(one, one)));
+/// assert_eq!(t.get_id(&Expr::Lit(1)), Some(one));
+/// assert_eq!(t.count(), 2);
+/// ```
+///
+/// Values can't be disinterned, since other values may refer to them. The
+/// greatest value of `I` is reserved, as in [`HashedArenaSet`].
+///
+/// # Type Parameters
+///
+///   * `T`: The type of interned values.
+///   * `I`: The "ID" type to uniquely resolve interned values.
+///   * `S`: The `BuildHasher` used to hash values.
+///
+/// [`StableDeref`]: trait.StableDeref.html
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`HashedArenaSet`]: struct.HashedArenaSet.html
+pub struct ConsTable<T, I = usize, S = RandomState> {
+    index: HashIndex<I>,
+    values: Vec<T>,
+    // the hash of the value in each slot
+    hashes: Vec<u64>,
+    max_idx: usize,
+    packing: Packing,
+    hasher: S,
+}
+
+impl<T, I, S> ConsTable<T, I, S>
+where I: Copy + Bounded + ToPrimitive + FromPrimitive
+{
+    /// Create a new, empty ConsTable with a default hasher.
+    #[inline]
+    pub fn new() -> Result<Self, Error>
+        where S: Default {
+        Self::with_hasher(S::default())
+    }
+
+    /// Create a new, empty ConsTable that hashes values with `hasher`.
+    pub fn with_hasher(hasher: S) -> Result<Self, Error> {
+        let packing = Packing::plain::<I>()?;
+        let max_idx = packing.max_idx::<I>()?.checked_sub(1).ok_or(Error::IdOverflow { max_idx: 0 })?;
+        Ok(ConsTable {
+            index: HashIndex::new(I::max_value()),
+            values: Vec::new(),
+            hashes: Vec::new(),
+            max_idx,
+            packing,
+            hasher,
+        })
+    }
+
+    /// Get the maximum index, i.e. one less than the number of unique IDs.
+    #[inline]
+    pub fn max_idx(&self) -> usize {
+        self.max_idx
+    }
+
+    /// Get the number of interned values.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Analogue of [`ArenaSet::resolve`].
+    ///
+    /// Complexity: _O(1)_
+    ///
+    /// [`ArenaSet::resolve`]: struct.ArenaSet.html#method.resolve
+    #[inline]
+    pub fn resolve<U: Borrow<I>>(&self, id: U) -> Result<&T, Error> {
+        let (ix, _) = self.packing.unpack(id.borrow().to_usize().ok_or(Error::FromIdFailed)?)?;
+        self.values.get(ix).ok_or(Error::InvalidId { index: ix })
+    }
+
+    /// Iterate over the values, in order of their IDs.
+    pub fn values(&self) -> slice::Iter<'_, T> {
+        self.values.iter()
+    }
+}
+
+impl<T, I, S> ConsTable<T, I, S>
+where T: Eq + Hash,
+      I: Copy + PartialEq + Bounded + ToPrimitive + FromPrimitive,
+      S: BuildHasher
+{
+    /// Analogue of [`ArenaSet::intern`].
+    ///
+    /// Complexity: _O(1)_ expected, plus one hash of `value`
+    ///
+    /// [`ArenaSet::intern`]: struct.ArenaSet.html#method.intern
+    pub fn intern(&mut self, value: T) -> Result<I, Error> {
+        let hash = self.hasher.hash_one(&value);
+        if let Some(id) = self.find(hash, &value) {
+            return Ok(id);
+        }
+        // don't let IDs overflow
+        let ix = self.values.len();
+        if ix != 0 && ix - 1 == self.max_idx {
+            return Err(Error::IdOverflow { max_idx: self.max_idx });
+        }
+        let id = I::from_usize(self.packing.pack(ix, &[])).ok_or(Error::ToIdFailed)?;
+        self.values.push(value);
+        self.hashes.push(hash);
+        let (hashes, packing) = (&self.hashes, &self.packing);
+        self.index.insert(hash, id, ix, |id| hashes[slot_of(packing, id)]);
+        Ok(id)
+    }
+
+    /// Analogue of [`ArenaSet::get_id`].
+    ///
+    /// Complexity: _O(1)_ expected, plus one hash of `value`
+    ///
+    /// [`ArenaSet::get_id`]: struct.ArenaSet.html#method.get_id
+    #[inline]
+    pub fn get_id(&self, value: &T) -> Option<I> {
+        self.find(self.hasher.hash_one(value), value)
+    }
+
+    /// Analogue of [`ArenaSet::contains`].
+    ///
+    /// [`ArenaSet::contains`]: struct.ArenaSet.html#method.contains
+    #[inline]
+    pub fn contains(&self, value: &T) -> bool {
+        self.get_id(value).is_some()
+    }
+
+    // Find the ID of `value`, comparing it only to values with the same hash.
+    fn find(&self, hash: u64, value: &T) -> Option<I> {
+        self.index.find(hash, |id| {
+            let ix = slot_of(&self.packing, id);
+            self.hashes[ix] == hash && self.values[ix] == *value
+        })
+    }
+}
@@ -0,0 +1,210 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use num_traits::{Bounded, ToPrimitive, FromPrimitive};
+use stable_deref_trait::StableDeref;
+
+use traits::{Map, NewMap};
+
+use super::{ArenaSet, Error, Iter, Slot, Packing, make_static};
+
+/// An immutable [`ArenaSet`], produced by [`freeze`].
+///
+/// Since nothing can be interned or disinterned anymore, the free list and
+/// maximum index are dropped, and the slots are kept in a boxed slice. All
+/// methods take `&self`, so a `FrozenArenaSet` is `Send + Sync` whenever its
+/// items are, and can be cheaply shared across threads behind an `Arc`.
+///
+/// The map is kept for reverse lookup with [`get_id`], unless it's discarded
+/// by [`without_index`].
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let mut p = shawshank::string_arena_set();
+/// assert_eq!(p.intern("hello"), Ok(0));
+/// assert_eq!(p.intern("world"), Ok(1));
+/// let frozen = Arc::new(p.freeze());
+/// let f = frozen.clone();
+/// thread::spawn(move || {
+///     assert_eq!(f.resolve(1), Ok("world"));
+///     assert_eq!(f.get_id("hello"), Some(0));
+/// }).join().unwrap();
+/// ```
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`freeze`]: struct.ArenaSet.html#method.freeze
+/// [`get_id`]: struct.FrozenArenaSet.html#method.get_id
+/// [`without_index`]: struct.FrozenArenaSet.html#method.without_index
+pub struct FrozenArenaSet<O: StableDeref, I = usize, M = HashMap<&'static < O as Deref >::Target, I>> {
+    map: Option<M>,
+    interned: Box<[Slot<O>]>,
+    count: usize,
+    packing: Packing,
+    generations: Box<[usize]>,
+    _i: PhantomData<I>,
+}
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      M: Map
+{
+    /// Turn this into an immutable [`FrozenArenaSet`]. All IDs stay the same.
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// let frozen = p.freeze();
+    /// assert_eq!(frozen.resolve(0), Ok("hello"));
+    /// ```
+    ///
+    /// [`FrozenArenaSet`]: struct.FrozenArenaSet.html
+    pub fn freeze(self) -> FrozenArenaSet<O, I, M> {
+        FrozenArenaSet {
+            count: self.map.len(),
+            map: Some(self.map),
+            interned: self.interned.into_boxed_slice(),
+            packing: self.packing,
+            generations: self.generations.into_boxed_slice(),
+            _i: PhantomData,
+        }
+    }
+}
+
+impl<O, I, M> FrozenArenaSet<O, I, M>
+where O: StableDeref,
+      I: ToPrimitive + FromPrimitive
+{
+    /// Get the number of interned items.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Analogue of [`ArenaSet::resolve`].
+    ///
+    /// Complexity: _O(1)_
+    ///
+    /// [`ArenaSet::resolve`]: struct.ArenaSet.html#method.resolve
+    #[inline]
+    pub fn resolve<U, Q: ? Sized>(&self, id: U) -> Result<&Q, Error>
+        where U: Borrow<I>,
+              O: Borrow<Q> {
+        let raw = id.borrow().to_usize().ok_or(Error::FromIdFailed)?;
+        match self.interned[self.packing.locate(raw, &self.interned, &self.generations)?] {
+            Slot::Occupied(ref item) => Ok(item.borrow()),
+            // invariant: `locate` only returns indices of occupied slots.
+            Slot::Vacant(_) => unreachable!(),
+        }
+    }
+
+    /// Analogue of [`ArenaSet::iter`].
+    ///
+    /// [`ArenaSet::iter`]: struct.ArenaSet.html#method.iter
+    pub fn iter(&self) -> Iter<'_, O, I> {
+        Iter::new(&self.interned, self.count, self.packing, &self.generations)
+    }
+
+    /// Discard the map used for reverse lookup, to save memory.
+    ///
+    /// Afterwards, [`get_id`] always returns `None`.
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// let frozen = p.freeze().without_index();
+    /// assert_eq!(frozen.resolve(0), Ok("hello"));
+    /// assert_eq!(frozen.get_id("hello"), None);
+    /// ```
+    ///
+    /// [`get_id`]: struct.FrozenArenaSet.html#method.get_id
+    pub fn without_index(mut self) -> Self {
+        self.map = None;
+        self
+    }
+
+    /// Check whether the map for reverse lookup is still present.
+    #[inline]
+    pub fn has_index(&self) -> bool {
+        self.map.is_some()
+    }
+}
+
+impl<O, I, M> FrozenArenaSet<O, I, M>
+where O: StableDeref,
+      O::Target: 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Key = &'static O::Target, Value = I>
+{
+    /// Analogue of [`ArenaSet::get_id`].
+    ///
+    /// Complexity: _O([`M::get(K)`])_
+    ///
+    /// [`ArenaSet::get_id`]: struct.ArenaSet.html#method.get_id
+    /// [`M::get(K)`]: trait.Map.html#tymethod.get
+    #[inline]
+    pub fn get_id(&self, item: &O::Target) -> Option<I> {
+        self.map.as_ref().and_then(|map| map.get(make_static(item)).cloned())
+    }
+
+    /// Analogue of [`ArenaSet::contains`].
+    ///
+    /// [`ArenaSet::contains`]: struct.ArenaSet.html#method.contains
+    #[inline]
+    pub fn contains(&self, item: &O::Target) -> bool {
+        self.get_id(item).is_some()
+    }
+
+    /// Turn this back into a mutable [`ArenaSet`], with the same IDs.
+    ///
+    /// The free list and, if it was discarded, the map are rebuilt. The
+    /// maximum index is reset to the widest the ID type allows.
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.intern("world"), Ok(1));
+    /// p.disintern(0).unwrap();
+    /// let mut p = p.freeze().without_index().thaw().unwrap();
+    /// assert_eq!(p.get_id("world"), Some(1));
+    /// assert_eq!(p.intern("again"), Ok(0));
+    /// ```
+    ///
+    /// [`ArenaSet`]: struct.ArenaSet.html
+    pub fn thaw(self) -> Result<ArenaSet<O, I, M>, Error>
+        where M: NewMap {
+        let max_idx = self.packing.max_idx::<I>()?;
+        let interned = self.interned.into_vec();
+        let generations = self.generations.into_vec();
+        let map = match self.map {
+            Some(map) => map,
+            None => {
+                let mut map = M::with_capacity(self.count);
+                for (ix, slot) in interned.iter().enumerate() {
+                    if let Slot::Occupied(ref item) = *slot {
+                        let id = I::from_usize(self.packing.pack(ix, &generations)).ok_or(Error::ToIdFailed)?;
+                        map.insert(make_static(item.deref()), id);
+                    }
+                }
+                map
+            }
+        };
+        let mut p = ArenaSet {
+            map,
+            interned,
+            head: !0,
+            max_idx,
+            packing: self.packing,
+            generations,
+            refcounts: None,
+            hits: None,
+            sorted: None,
+            _i: PhantomData,
+        };
+        p.relink_vacant();
+        Ok(p)
+    }
+}
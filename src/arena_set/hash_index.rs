@@ -0,0 +1,128 @@
+use std::cmp;
+use std::mem;
+
+use num_traits::ToPrimitive;
+
+use super::Packing;
+
+// The table never gets fuller than `MAX_LOAD_NUM / MAX_LOAD_DEN`.
+const MAX_LOAD_NUM: usize = 7;
+const MAX_LOAD_DEN: usize = 8;
+const MIN_TABLE_LEN: usize = 8;
+
+// An open-addressing hash table holding nothing but IDs, for sets that cache
+// the hash of each item next to its slot. The table never hashes anything
+// itself: callers pass the hash of the item looked up, and a function giving
+// the cached hash of the item behind an ID wherever entries move.
+pub(super) struct HashIndex<I> {
+    // IDs, or `empty`; the length is zero or a power of two, and at least
+    // one entry is always empty.
+    table: Vec<I>,
+    empty: I,
+}
+
+impl<I> HashIndex<I> {
+    // Create an empty index, with `empty` marking empty entries; that ID
+    // can't be stored.
+    pub(super) fn new(empty: I) -> Self {
+        HashIndex {
+            table: Vec::new(),
+            empty,
+        }
+    }
+}
+
+impl<I: Copy + PartialEq> HashIndex<I> {
+    // Find the ID in the probe sequence of `hash` for which `matches` holds.
+    pub(super) fn find<F: Fn(I) -> bool>(&self, hash: u64, matches: F) -> Option<I> {
+        if self.table.is_empty() {
+            return None;
+        }
+        let mask = self.table.len() - 1;
+        let mut pos = hash as usize & mask;
+        loop {
+            let id = self.table[pos];
+            if id == self.empty {
+                return None;
+            }
+            if matches(id) {
+                return Some(id);
+            }
+            pos = (pos + 1) & mask;
+        }
+    }
+
+    // Add `id` under `hash`, growing first if `count` entries plus this one
+    // would be over the load factor.
+    pub(super) fn insert<H: Fn(I) -> u64>(&mut self, hash: u64, id: I, count: usize, hash_of: H) {
+        if (count + 1) * MAX_LOAD_DEN > self.table.len() * MAX_LOAD_NUM {
+            let len = cmp::max(MIN_TABLE_LEN, self.table.len() * 2);
+            let (old, empty) = (self.resize(len), self.empty);
+            for id in old.into_iter().filter(|&id| id != empty) {
+                self.put(hash_of(id), id);
+            }
+        }
+        self.put(hash, id);
+    }
+
+    // Remove `id`, which must be present under `hash`, moving later entries
+    // of the same cluster back into its place unless that would put them
+    // before their ideal position.
+    pub(super) fn remove<H: Fn(I) -> u64>(&mut self, hash: u64, id: I, hash_of: H) {
+        let mask = self.table.len() - 1;
+        let mut hole = hash as usize & mask;
+        while self.table[hole] != id {
+            hole = (hole + 1) & mask;
+        }
+        let mut pos = (hole + 1) & mask;
+        while self.table[pos] != self.empty {
+            let ideal = hash_of(self.table[pos]) as usize & mask;
+            if pos.wrapping_sub(ideal) & mask >= pos.wrapping_sub(hole) & mask {
+                self.table[hole] = self.table[pos];
+                hole = pos;
+            }
+            pos = (pos + 1) & mask;
+        }
+        self.table[hole] = self.empty;
+    }
+
+    // Replace the contents with `count` entries of `(hash, id)`, in the
+    // smallest table that holds them within the load factor.
+    pub(super) fn rebuild<E>(&mut self, count: usize, entries: E)
+        where E: IntoIterator<Item = (u64, I)> {
+        let len = if count == 0 {
+            0
+        } else {
+            cmp::max(MIN_TABLE_LEN, (count * MAX_LOAD_DEN / MAX_LOAD_NUM + 1).next_power_of_two())
+        };
+        self.resize(len);
+        for (hash, id) in entries {
+            self.put(hash, id);
+        }
+    }
+
+    // Empty the table and give it `len` entries, returning the old ones.
+    fn resize(&mut self, len: usize) -> Vec<I> {
+        mem::replace(&mut self.table, vec![self.empty; len])
+    }
+
+    // Store `id` in the first empty entry in the probe sequence of `hash`.
+    fn put(&mut self, hash: u64, id: I) {
+        let mask = self.table.len() - 1;
+        let mut pos = hash as usize & mask;
+        while self.table[pos] != self.empty {
+            pos = (pos + 1) & mask;
+        }
+        self.table[pos] = id;
+    }
+}
+
+// The slot index of an ID taken from a `HashIndex`.
+#[inline]
+pub(super) fn slot_of<I: ToPrimitive>(packing: &Packing, id: I) -> usize {
+    // invariant: the index only holds IDs converted from slot indices.
+    match id.to_usize() {
+        Some(raw) => raw - packing.offset,
+        None => unreachable!(),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,310 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::mem;
+
+use num_traits::{Bounded, ToPrimitive, FromPrimitive};
+use stable_deref_trait::StableDeref;
+
+use traits::{NewMap, Interner};
+
+use super::{Error, Iter, Slot, Packing};
+use super::hash_index::{HashIndex, slot_of};
+
+/// An [`ArenaSet`] with its own hash index instead of a [`Map`].
+///
+/// A 64-bit hash is computed once per item and kept next to its slot, and
+/// the index is an open-addressing table holding nothing but IDs. Looking
+/// an item up hashes it once, and only compares it to items whose cached
+/// hash matches; growing the table never hashes an item again. Removals
+/// shift later entries back rather than leaving tombstones, so lookups don't
+/// slow down after many calls to [`disintern`].
+///
+/// Compared to a `HashMap<&'static O::Target, I>`, which stores a key
+/// reference and an ID per entry, each item costs 8 bytes of hash plus
+/// about `size_of::<I>() * 8 / 7` bytes of table, roughly half as much for
+/// `I = u32` and string or slice items.
+///
+/// IDs are never [generational], and the greatest value of `I` is reserved
+/// to mark empty table entries, so one fewer item fits than in an
+/// `ArenaSet`.
+///
+/// ```
+/// let mut p = shawshank::Builder::<String, u32>::new().hashed().unwrap();
+/// assert_eq!(p.intern("hello"), Ok(0));
+/// assert_eq!(p.intern("world"), Ok(1));
+/// assert_eq!(p.intern("hello"), Ok(0));
+/// assert_eq!(p.resolve(1), Ok("world"));
+/// assert_eq!(p.disintern(0), Ok("hello".into()));
+/// assert_eq!(p.get_id("hello"), None);
+/// assert_eq!(p.get_id("world"), Some(1));
+/// ```
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`Map`]: trait.Map.html
+/// [`disintern`]: struct.HashedArenaSet.html#method.disintern
+/// [generational]: struct.ArenaSet.html#generational-ids
+pub struct HashedArenaSet<O: StableDeref, I = usize, S = RandomState> {
+    index: HashIndex<I>,
+    interned: Vec<Slot<O>>,
+    // the hash of the item in each slot, stale for vacant ones
+    hashes: Vec<u64>,
+    head: usize,
+    count: usize,
+    max_idx: usize,
+    packing: Packing,
+    hasher: S,
+}
+
+impl<O, I, S> HashedArenaSet<O, I, S>
+where O: StableDeref,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive
+{
+    /// Create an empty set with a default hasher.
+    pub fn new() -> Result<Self, Error>
+        where S: Default {
+        Self::with_hasher(S::default())
+    }
+
+    /// Create an empty set that hashes items with `hasher`.
+    pub fn with_hasher(hasher: S) -> Result<Self, Error> {
+        let packing = Packing::plain::<I>()?;
+        let max_idx = packing.max_idx::<I>()?.checked_sub(1).ok_or(Error::IdOverflow { max_idx: 0 })?;
+        Ok(HashedArenaSet {
+            index: HashIndex::new(I::max_value()),
+            interned: Vec::new(),
+            hashes: Vec::new(),
+            head: !0,
+            count: 0,
+            max_idx,
+            packing,
+            hasher,
+        })
+    }
+
+    /// Get the maximum index, i.e. one less than the number of unique IDs.
+    #[inline]
+    pub fn max_idx(&self) -> usize {
+        self.max_idx
+    }
+
+    /// Get the number of interned items.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Analogue of [`ArenaSet::resolve`].
+    ///
+    /// Complexity: _O(1)_
+    ///
+    /// [`ArenaSet::resolve`]: struct.ArenaSet.html#method.resolve
+    #[inline]
+    pub fn resolve<U, Q: ? Sized>(&self, id: U) -> Result<&Q, Error>
+        where U: Borrow<I>,
+              O: Borrow<Q> {
+        match self.interned[self.index_of(id.borrow())?] {
+            Slot::Occupied(ref item) => Ok(item.borrow()),
+            // invariant: `index_of` only returns indices of occupied slots.
+            Slot::Vacant(_) => unreachable!(),
+        }
+    }
+
+    /// Analogue of [`ArenaSet::iter`].
+    ///
+    /// [`ArenaSet::iter`]: struct.ArenaSet.html#method.iter
+    pub fn iter(&self) -> Iter<'_, O, I> {
+        Iter::new(&self.interned, self.count, self.packing, &[])
+    }
+
+    #[inline]
+    fn to_id(&self, ix: usize) -> Result<I, Error> {
+        I::from_usize(self.packing.pack(ix, &[])).ok_or(Error::ToIdFailed)
+    }
+
+    #[inline]
+    fn index_of(&self, id: &I) -> Result<usize, Error> {
+        self.packing.locate(id.to_usize().ok_or(Error::FromIdFailed)?, &self.interned, &[])
+    }
+
+}
+
+
+impl<O, I, S> HashedArenaSet<O, I, S>
+where O: StableDeref,
+      O::Target: Eq + Hash,
+      I: Copy + PartialEq + Bounded + ToPrimitive + FromPrimitive,
+      S: BuildHasher
+{
+    /// Analogue of [`ArenaSet::intern`].
+    ///
+    /// Complexity: _O(1)_ expected, plus one hash of `item`
+    ///
+    /// [`ArenaSet::intern`]: struct.ArenaSet.html#method.intern
+    pub fn intern<Q>(&mut self, item: Q) -> Result<I, Error>
+        where Q: Borrow<O::Target>,
+              O: From<Q> {
+        let hash = self.hasher.hash_one(item.borrow());
+        if let Some(id) = self.find(hash, item.borrow()) {
+            return Ok(id);
+        }
+        // don't let IDs overflow
+        if self.count != 0 && self.count - 1 == self.max_idx {
+            return Err(Error::IdOverflow { max_idx: self.max_idx });
+        }
+        // invariant: if `self.head != !0`, then it has an index to vacant
+        // slot; otherwise there are none.
+        let ix = if self.head == !0 { self.interned.len() } else { self.head };
+        let id = self.to_id(ix)?;
+        let owned = O::from(item);
+        if ix == self.interned.len() {
+            self.interned.push(Slot::Occupied(owned));
+            self.hashes.push(hash);
+        } else {
+            if let Slot::Vacant(next) = mem::replace(&mut self.interned[ix], Slot::Occupied(owned)) {
+                self.head = next;
+            }
+            self.hashes[ix] = hash;
+        }
+        let (hashes, packing) = (&self.hashes, &self.packing);
+        self.index.insert(hash, id, self.count, |id| hashes[slot_of(packing, id)]);
+        self.count += 1;
+        Ok(id)
+    }
+
+    /// Analogue of [`ArenaSet::get_id`].
+    ///
+    /// Complexity: _O(1)_ expected, plus one hash of `item`
+    ///
+    /// [`ArenaSet::get_id`]: struct.ArenaSet.html#method.get_id
+    #[inline]
+    pub fn get_id(&self, item: &O::Target) -> Option<I> {
+        self.find(self.hasher.hash_one(item), item)
+    }
+
+    /// Analogue of [`ArenaSet::contains`].
+    ///
+    /// [`ArenaSet::contains`]: struct.ArenaSet.html#method.contains
+    #[inline]
+    pub fn contains(&self, item: &O::Target) -> bool {
+        self.get_id(item).is_some()
+    }
+
+    /// Analogue of [`ArenaSet::disintern`].
+    ///
+    /// The item isn't hashed again: its entry is found by the cached hash
+    /// and its ID.
+    ///
+    /// Complexity: _O(1)_ expected
+    ///
+    /// [`ArenaSet::disintern`]: struct.ArenaSet.html#method.disintern
+    pub fn disintern<U: Borrow<I>>(&mut self, id: U) -> Result<O, Error> {
+        let id = *id.borrow();
+        let ix = self.index_of(&id)?;
+        let (hashes, packing) = (&self.hashes, &self.packing);
+        self.index.remove(hashes[ix], id, |id| hashes[slot_of(packing, id)]);
+        self.count -= 1;
+        match mem::replace(&mut self.interned[ix], Slot::Vacant(self.head)) {
+            Slot::Occupied(item) => {
+                self.head = ix;
+                Ok(item)
+            }
+            // invariant: `index_of` only returns indices of occupied slots.
+            Slot::Vacant(_) => unreachable!(),
+        }
+    }
+
+    /// Analogue of [`ArenaSet::shrink`]. The index is rebuilt from the
+    /// cached hashes.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut p = shawshank::builder::<String>().hashed().unwrap();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.intern("world"), Ok(1));
+    /// p.disintern(0).unwrap();
+    /// let remap: BTreeMap<_, _> = p.shrink();
+    /// assert_eq!(remap[&1], 0);
+    /// assert_eq!(p.get_id("world"), Some(0));
+    /// ```
+    ///
+    /// Complexity: _O(n + n * [`T::insert(K)`])_
+    ///
+    /// [`ArenaSet::shrink`]: struct.ArenaSet.html#method.shrink
+    /// [`T::insert(K)`]: trait.Map.html#tymethod.insert
+    pub fn shrink<T: NewMap<Key = I, Value = I>>(&mut self) -> T {
+        let mut remap = T::new();
+        let interned = mem::take(&mut self.interned);
+        let hashes = mem::take(&mut self.hashes);
+        for (ix, (slot, hash)) in interned.into_iter().zip(hashes).enumerate() {
+            if let Slot::Occupied(item) = slot {
+                if let (Ok(old_id), Ok(new_id)) = (self.to_id(ix), self.to_id(self.interned.len())) {
+                    remap.insert(old_id, new_id);
+                    self.interned.push(Slot::Occupied(item));
+                    self.hashes.push(hash);
+                }
+            }
+        }
+        self.count = self.interned.len();
+        // invariant: no vacant slots
+        self.head = !0;
+        self.interned.shrink_to_fit();
+        self.hashes.shrink_to_fit();
+        // invariant: the slots were just converted to IDs.
+        let packing = self.packing;
+        let entries = self.hashes.iter().enumerate()
+            .filter_map(|(ix, &hash)| I::from_usize(packing.pack(ix, &[])).map(|id| (hash, id)));
+        self.index.rebuild(self.count, entries);
+        remap
+    }
+
+    // Find the ID of `item`, comparing it only to items with the same hash.
+    fn find(&self, hash: u64, item: &O::Target) -> Option<I> {
+        self.index.find(hash, |id| {
+            let ix = slot_of(&self.packing, id);
+            self.hashes[ix] == hash && match self.interned[ix] {
+                Slot::Occupied(ref other) => other.deref() == item,
+                Slot::Vacant(_) => false,
+            }
+        })
+    }
+}
+
+impl<O, I, S> Interner for HashedArenaSet<O, I, S>
+where O: StableDeref,
+      O::Target: Eq + Hash,
+      I: Copy + PartialEq + Bounded + ToPrimitive + FromPrimitive,
+      S: BuildHasher
+{
+    type Item = O::Target;
+    type Input = O;
+    type Owned = O;
+    type Id = I;
+
+    fn intern<Q>(&mut self, item: Q) -> Result<I, Error>
+        where Q: Borrow<O::Target>,
+              O: From<Q> {
+        HashedArenaSet::intern(self, item)
+    }
+
+    fn resolve<U: Borrow<I>>(&self, id: U) -> Result<&O::Target, Error> {
+        match self.interned[self.index_of(id.borrow())?] {
+            Slot::Occupied(ref item) => Ok(item.deref()),
+            // invariant: `index_of` only returns indices of occupied slots.
+            Slot::Vacant(_) => unreachable!(),
+        }
+    }
+
+    fn disintern<U: Borrow<I>>(&mut self, id: U) -> Result<O, Error> {
+        HashedArenaSet::disintern(self, id)
+    }
+
+    fn shrink<T: NewMap<Key = I, Value = I>>(&mut self) -> T {
+        HashedArenaSet::shrink(self)
+    }
+
+    fn count(&self) -> usize {
+        HashedArenaSet::count(self)
+    }
+}
@@ -0,0 +1,195 @@
+use std::borrow::Borrow;
+use std::ops::Deref;
+use std::sync::atomic::AtomicUsize;
+
+use num_traits::{Bounded, ToPrimitive, FromPrimitive};
+use stable_deref_trait::StableDeref;
+
+use traits::{Map, NewMap};
+
+use super::{ArenaSet, StadiumSet, Error, Slot};
+
+impl<O: StableDeref, I, M> ArenaSet<O, I, M> {
+    /// Turn on hit counting.
+    ///
+    /// Every call to [`intern`] or [`resolve`] then counts a hit of the item,
+    /// so that [`renumber_by_frequency`] can give the most used items the
+    /// smallest IDs. Items that are already interned start without hits.
+    /// Since `resolve` only borrows the set, hits are counted atomically.
+    ///
+    /// Counts aren't preserved by [freezing], snapshots or serialization.
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set().hit_counted();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.resolve::<_, str>(0), Ok("hello"));
+    /// assert_eq!(p.hits(0), Ok(3));
+    /// ```
+    ///
+    /// [`intern`]: struct.ArenaSet.html#method.intern
+    /// [`resolve`]: struct.ArenaSet.html#method.resolve
+    /// [`renumber_by_frequency`]: struct.ArenaSet.html#method.renumber_by_frequency
+    /// [freezing]: struct.ArenaSet.html#method.freeze
+    pub fn hit_counted(mut self) -> Self {
+        if self.hits.is_none() {
+            self.hits = Some((0..self.interned.len()).map(|_| AtomicUsize::new(0)).collect());
+        }
+        self
+    }
+
+    /// Check whether hits of items are counted.
+    #[inline]
+    pub fn is_hit_counted(&self) -> bool {
+        self.hits.is_some()
+    }
+}
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map
+{
+    /// Get the number of hits of an interned item.
+    ///
+    /// This is always zero if the set isn't [hit counted].
+    ///
+    /// [hit counted]: struct.ArenaSet.html#method.hit_counted
+    pub fn hits<U: Borrow<I>>(&self, id: U) -> Result<usize, Error> {
+        let ix = self.index_of(id.borrow())?;
+        Ok(self.hits_at(ix))
+    }
+}
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      O::Target: 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Key = &'static O::Target, Value = I>
+{
+    /// Renumber the items by descending number of [hits], so that the most
+    /// used ones get the smallest IDs. Returns a map from the old IDs to the
+    /// new ones.
+    ///
+    /// Like [`shrink`], this re-uses the IDs of disinterned items. Items with
+    /// the same number of hits keep their relative order.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut p = shawshank::string_arena_set().hit_counted();
+    /// assert_eq!(p.intern("rare"), Ok(0));
+    /// assert_eq!(p.intern("common"), Ok(1));
+    /// assert_eq!(p.intern("common"), Ok(1));
+    /// let remap: BTreeMap<_, _> = p.renumber_by_frequency().unwrap();
+    /// assert_eq!(remap[&1], 0);
+    /// assert_eq!(p.resolve(0), Ok("common"));
+    /// assert_eq!(p.hits(0), Ok(3));
+    /// ```
+    ///
+    /// With [generational IDs], the old IDs of moved items go stale, as do
+    /// those of items disinterned before, even with a single generation bit:
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut p = shawshank::builder::<String>().generational(1).hit_counted().hash().unwrap();
+    /// let a = p.intern("a").unwrap();
+    /// let b = p.intern("b").unwrap();
+    /// p.disintern(a).unwrap();
+    /// let remap: BTreeMap<_, _> = p.renumber_by_frequency().unwrap();
+    /// assert!(p.resolve::<_, str>(a).is_err());
+    /// assert!(p.resolve::<_, str>(b).is_err());
+    /// assert_eq!(p.resolve(remap[&b]), Ok("b"));
+    /// ```
+    ///
+    /// Complexity: _O(n log n + n * [`T::insert(K)`] + n * [`M::insert(K, V)`])_
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Unsupported`] if the set is [sorted], since that
+    /// order would be lost; the set is then left as it was.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut p = shawshank::builder::<String>().hit_counted().sorted(4).btree().unwrap();
+    /// assert_eq!(p.intern("a"), Ok(3));
+    /// assert!(p.renumber_by_frequency::<BTreeMap<_, _>>().is_err());
+    /// assert_eq!(p.resolve(3), Ok("a"));
+    /// ```
+    ///
+    /// [hits]: struct.ArenaSet.html#method.hit_counted
+    /// [`shrink`]: struct.ArenaSet.html#method.shrink
+    /// [sorted]: struct.ArenaSet.html#method.sorted
+    /// [`Error::Unsupported`]: enum.Error.html#variant.Unsupported
+    /// [generational IDs]: struct.ArenaSet.html#generational-ids
+    /// [`T::insert(K)`]: trait.Map.html#tymethod.insert
+    /// [`M::insert(K, V)`]: trait.Map.html#tymethod.insert
+    pub fn renumber_by_frequency<T: NewMap<Key = I, Value = I>>(&mut self) -> Result<T, Error> {
+        self.renumber_by_frequency_with(|item: &O| item.deref())
+    }
+}
+
+// The logic shared by `ArenaSet` and `StadiumSet`, as in `arena_set.rs`.
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Value = I>
+{
+    fn renumber_by_frequency_with<T, K, G>(&mut self, key: G) -> Result<T, Error>
+        where T: NewMap<Key = I, Value = I>,
+              K: ? Sized + 'static,
+              M: Map<Key = &'static K>,
+              G: Fn(&O) -> &K {
+        if self.sorted.is_some() {
+            return Err(Error::Unsupported("a sorted set can't be renumbered by frequency"));
+        }
+        let hits: Vec<usize> = self.interned.iter().enumerate().map(|(ix, slot)| match *slot {
+            Slot::Occupied(_) => self.hits_at(ix),
+            Slot::Vacant(_) => 0,
+        }).collect();
+        // the sort is stable, and the items start in the order of their slots
+        Ok(self.renumber_with(key, 1, |items| items.sort_by(|a, b| hits[b.0].cmp(&hits[a.0]))))
+    }
+}
+
+impl<O, R, I, M> StadiumSet<O, R, I, M>
+where O: StableDeref<Target = R>,
+      R: 'static + StableDeref,
+      I: Copy + ToPrimitive + FromPrimitive + Bounded,
+      M: Map<Key = &'static < R as Deref >::Target, Value = I>
+{
+    /// Analogue of [`hit_counted`].
+    ///
+    /// [`hit_counted`]: struct.ArenaSet.html#method.hit_counted
+    pub fn hit_counted(self) -> Self {
+        StadiumSet(self.0.hit_counted())
+    }
+
+    /// Analogue of [`hits`].
+    ///
+    /// [`hits`]: struct.ArenaSet.html#method.hits
+    pub fn hits<U: Borrow<I>>(&self, id: U) -> Result<usize, Error> {
+        self.0.hits(id)
+    }
+
+    /// Analogue of [`renumber_by_frequency`].
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// let mut p = shawshank::byte_stadium_set().hit_counted();
+    /// assert_eq!(p.intern(vec![1]), Ok(0));
+    /// assert_eq!(p.intern(vec![2]), Ok(1));
+    /// assert_eq!(p.resolve::<_, Vec<u8>>(1), Ok(&vec![2]));
+    /// let remap: HashMap<_, _> = p.renumber_by_frequency().unwrap();
+    /// assert_eq!(remap[&1], 0);
+    /// assert_eq!(remap[&0], 1);
+    /// ```
+    ///
+    /// [`renumber_by_frequency`]: struct.ArenaSet.html#method.renumber_by_frequency
+    pub fn renumber_by_frequency<T: NewMap<Key = I, Value = I>>(&mut self) -> Result<T, Error> {
+        self.0.renumber_by_frequency_with(|item: &O| item.deref().deref())
+    }
+}
@@ -0,0 +1,301 @@
+use std::iter::Enumerate;
+use std::marker::PhantomData;
+use std::slice;
+use std::vec;
+
+use num_traits::FromPrimitive;
+use stable_deref_trait::StableDeref;
+
+use traits::Map;
+
+use super::{ArenaSet, StadiumSet, Slot, Packing};
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      I: FromPrimitive,
+      M: Map
+{
+    /// Iterate over the interned items and their IDs, in ID order.
+    ///
+    /// Vacant slots left behind by [`disintern`] are skipped.
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.intern("world"), Ok(1));
+    /// assert_eq!(p.intern("!"), Ok(2));
+    /// p.disintern(1).unwrap();
+    /// let items: Vec<_> = p.iter().map(|(id, s)| (id, s.as_str())).collect();
+    /// assert_eq!(items, vec![(0, "hello"), (2, "!")]);
+    /// ```
+    ///
+    /// [`disintern`]: struct.ArenaSet.html#method.disintern
+    pub fn iter(&self) -> Iter<'_, O, I> {
+        Iter::new(&self.interned, self.map.len(), self.packing, &self.generations)
+    }
+
+    /// Iterate over the IDs of interned items, in order.
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.intern("world"), Ok(1));
+    /// p.disintern(0).unwrap();
+    /// assert_eq!(p.ids().collect::<Vec<_>>(), vec![1]);
+    /// ```
+    pub fn ids(&self) -> Ids<'_, O, I> {
+        Ids(self.iter())
+    }
+
+    /// Iterate over the interned items, in ID order.
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.intern("world"), Ok(1));
+    /// assert_eq!(p.values().collect::<Vec<_>>(), vec!["hello", "world"]);
+    /// ```
+    pub fn values(&self) -> Values<'_, O, I> {
+        Values(self.iter())
+    }
+}
+
+impl<O, R, I, M> StadiumSet<O, R, I, M>
+where O: StableDeref<Target = R>,
+      R: ? Sized + StableDeref,
+      I: FromPrimitive,
+      M: Map
+{
+    /// Analogue of [`iter`].
+    ///
+    /// ```
+    /// let mut p = shawshank::byte_stadium_set();
+    /// assert_eq!(p.intern(&[1,2,3][..]), Ok(0));
+    /// assert_eq!(p.intern(&[4,5,6][..]), Ok(1));
+    /// let items: Vec<_> = p.iter().map(|(id, v)| (id, v.to_vec())).collect();
+    /// assert_eq!(items, vec![(0, vec![1,2,3]), (1, vec![4,5,6])]);
+    /// ```
+    ///
+    /// [`iter`]: struct.ArenaSet.html#method.iter
+    pub fn iter(&self) -> Iter<'_, O, I> {
+        self.0.iter()
+    }
+
+    /// Analogue of [`ids`].
+    ///
+    /// [`ids`]: struct.ArenaSet.html#method.ids
+    pub fn ids(&self) -> Ids<'_, O, I> {
+        self.0.ids()
+    }
+
+    /// Analogue of [`values`].
+    ///
+    /// [`values`]: struct.ArenaSet.html#method.values
+    pub fn values(&self) -> Values<'_, O, I> {
+        self.0.values()
+    }
+}
+
+impl<'a, O, I, M> IntoIterator for &'a ArenaSet<O, I, M>
+where O: StableDeref,
+      I: FromPrimitive,
+      M: Map
+{
+    type Item = (I, &'a O);
+    type IntoIter = Iter<'a, O, I>;
+
+    fn into_iter(self) -> Iter<'a, O, I> {
+        self.iter()
+    }
+}
+
+impl<O, I, M> IntoIterator for ArenaSet<O, I, M>
+where O: StableDeref,
+      I: FromPrimitive,
+      M: Map
+{
+    type Item = (I, O);
+    type IntoIter = IntoIter<O, I>;
+
+    /// Consume the set, yielding the interned items and their IDs, in ID order.
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.intern("world"), Ok(1));
+    /// let items: Vec<_> = p.into_iter().collect();
+    /// assert_eq!(items, vec![(0, "hello".into()), (1, "world".into())]);
+    /// ```
+    fn into_iter(self) -> IntoIter<O, I> {
+        // the map only borrows from `interned`, so it must go first.
+        let remaining = self.map.len();
+        let ArenaSet { map, interned, packing, generations, .. } = self;
+        drop(map);
+        IntoIter {
+            slots: interned.into_iter().enumerate(),
+            remaining,
+            packing,
+            generations,
+            _i: PhantomData,
+        }
+    }
+}
+
+impl<'a, O, R, I, M> IntoIterator for &'a StadiumSet<O, R, I, M>
+where O: StableDeref<Target = R>,
+      R: ? Sized + StableDeref,
+      I: FromPrimitive,
+      M: Map
+{
+    type Item = (I, &'a O);
+    type IntoIter = Iter<'a, O, I>;
+
+    fn into_iter(self) -> Iter<'a, O, I> {
+        self.iter()
+    }
+}
+
+impl<O, R, I, M> IntoIterator for StadiumSet<O, R, I, M>
+where O: StableDeref<Target = R>,
+      R: ? Sized + StableDeref,
+      I: FromPrimitive,
+      M: Map
+{
+    type Item = (I, O);
+    type IntoIter = IntoIter<O, I>;
+
+    fn into_iter(self) -> IntoIter<O, I> {
+        self.0.into_iter()
+    }
+}
+
+/// Iterator over the items of an [`ArenaSet`] and their IDs.
+///
+/// Created by [`ArenaSet::iter`].
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`ArenaSet::iter`]: struct.ArenaSet.html#method.iter
+pub struct Iter<'a, O: 'a, I> {
+    slots: Enumerate<slice::Iter<'a, Slot<O>>>,
+    remaining: usize,
+    packing: Packing,
+    generations: &'a [usize],
+    _i: PhantomData<I>,
+}
+
+impl<'a, O, I> Iter<'a, O, I> {
+    pub(super) fn new(slots: &'a [Slot<O>], remaining: usize, packing: Packing, generations: &'a [usize]) -> Self {
+        Iter {
+            slots: slots.iter().enumerate(),
+            remaining,
+            packing,
+            generations,
+            _i: PhantomData,
+        }
+    }
+}
+
+impl<'a, O, I: FromPrimitive> Iterator for Iter<'a, O, I> {
+    type Item = (I, &'a O);
+
+    fn next(&mut self) -> Option<(I, &'a O)> {
+        for (ix, slot) in &mut self.slots {
+            if let Slot::Occupied(ref item) = *slot {
+                // invariant: the index of an occupied slot was already
+                // converted to an ID when the item was interned.
+                if let Some(id) = I::from_usize(self.packing.pack(ix, self.generations)) {
+                    self.remaining -= 1;
+                    return Some((id, item));
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, O, I: FromPrimitive> ExactSizeIterator for Iter<'a, O, I> {}
+
+/// Iterator over the IDs of an [`ArenaSet`].
+///
+/// Created by [`ArenaSet::ids`].
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`ArenaSet::ids`]: struct.ArenaSet.html#method.ids
+pub struct Ids<'a, O: 'a, I>(Iter<'a, O, I>);
+
+impl<'a, O, I: FromPrimitive> Iterator for Ids<'a, O, I> {
+    type Item = I;
+
+    fn next(&mut self) -> Option<I> {
+        self.0.next().map(|(id, _)| id)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, O, I: FromPrimitive> ExactSizeIterator for Ids<'a, O, I> {}
+
+/// Iterator over the items of an [`ArenaSet`].
+///
+/// Created by [`ArenaSet::values`].
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`ArenaSet::values`]: struct.ArenaSet.html#method.values
+pub struct Values<'a, O: 'a, I>(Iter<'a, O, I>);
+
+impl<'a, O, I: FromPrimitive> Iterator for Values<'a, O, I> {
+    type Item = &'a O;
+
+    fn next(&mut self) -> Option<&'a O> {
+        self.0.next().map(|(_, item)| item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, O, I: FromPrimitive> ExactSizeIterator for Values<'a, O, I> {}
+
+/// Owning iterator over the items of an [`ArenaSet`] and their IDs.
+///
+/// Created by the `into_iter` method of `ArenaSet` (provided by the
+/// `IntoIterator` trait).
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+pub struct IntoIter<O, I> {
+    slots: Enumerate<vec::IntoIter<Slot<O>>>,
+    remaining: usize,
+    packing: Packing,
+    generations: Vec<usize>,
+    _i: PhantomData<I>,
+}
+
+impl<O, I: FromPrimitive> Iterator for IntoIter<O, I> {
+    type Item = (I, O);
+
+    fn next(&mut self) -> Option<(I, O)> {
+        for (ix, slot) in &mut self.slots {
+            if let Slot::Occupied(item) = slot {
+                // invariant: see `Iter::next`.
+                if let Some(id) = I::from_usize(self.packing.pack(ix, &self.generations)) {
+                    self.remaining -= 1;
+                    return Some((id, item));
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<O, I: FromPrimitive> ExactSizeIterator for IntoIter<O, I> {}
@@ -0,0 +1,240 @@
+use std::borrow::Borrow;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::ops::Deref;
+
+use num_traits::{Bounded, ToPrimitive, FromPrimitive};
+use stable_deref_trait::StableDeref;
+
+use traits::Map;
+
+use super::{ArenaSet, Error, Slot};
+
+/// An [`ArenaSet`] holding at most a fixed number of items, produced by
+/// [`lru`].
+///
+/// Once full, interning a new item first evicts the one least recently
+/// interned or resolved, whose slot is then reused through the free list.
+/// [`intern`] returns the evicted ID and item, so that anything depending on
+/// them can be invalidated. Combined with [generational IDs], the evicted ID
+/// is also rejected from then on.
+///
+/// Recency is tracked by a doubly-linked list threaded through the slot
+/// indices, so every operation stays _O(1)_ on top of the map.
+///
+/// ```
+/// let mut p = shawshank::string_arena_set().lru(2);
+/// assert_eq!(p.intern("a"), Ok((0, None)));
+/// assert_eq!(p.intern("b"), Ok((1, None)));
+/// assert_eq!(p.resolve(0), Ok("a"));
+/// // "b" is now the least recently used
+/// assert_eq!(p.intern("c"), Ok((1, Some((1, "b".into())))));
+/// assert!(!p.contains("b"));
+/// assert_eq!(p.intern("a"), Ok((0, None)));
+/// ```
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`lru`]: struct.ArenaSet.html#method.lru
+/// [`intern`]: struct.LruArenaSet.html#method.intern
+/// [generational IDs]: struct.ArenaSet.html#generational-ids
+pub struct LruArenaSet<O: StableDeref, I = usize, M = HashMap<&'static < O as Deref >::Target, I>> {
+    set: ArenaSet<O, I, M>,
+    capacity: usize,
+    // neighbours of each slot in the recency list, with `!0` as the end
+    newer: Vec<Cell<usize>>,
+    older: Vec<Cell<usize>>,
+    newest: Cell<usize>,
+    oldest: Cell<usize>,
+}
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      M: Map
+{
+    /// Limit this set to `capacity` items, evicting the least recently used
+    /// to make room for new ones.
+    ///
+    /// Items already interned are considered used in order of their indices.
+    /// A capacity beyond the maximum index is reduced to fit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero, or less than the number of items.
+    pub fn lru(self, capacity: usize) -> LruArenaSet<O, I, M> {
+        assert!(capacity != 0, "an LruArenaSet needs a capacity of at least one");
+        assert!(capacity >= self.map.len(), "an LruArenaSet can't start with more items than its capacity");
+        let p = LruArenaSet {
+            capacity: capacity.min(self.max_idx.saturating_add(1)),
+            newer: vec![Cell::new(!0); self.interned.len()],
+            older: vec![Cell::new(!0); self.interned.len()],
+            newest: Cell::new(!0),
+            oldest: Cell::new(!0),
+            set: self,
+        };
+        let occupied: Vec<_> = p.set.interned.iter().enumerate()
+            .filter(|&(_, slot)| match *slot {
+                Slot::Occupied(_) => true,
+                Slot::Vacant(_) => false,
+            })
+            .map(|(ix, _)| ix)
+            .collect();
+        for ix in occupied {
+            p.push_newest(ix);
+        }
+        p
+    }
+}
+
+impl<O, I, M> LruArenaSet<O, I, M>
+where O: StableDeref,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map
+{
+    /// Get the maximum number of items.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the number of interned items.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.set.count()
+    }
+
+    /// Analogue of [`ArenaSet::resolve`], which also marks the item as the
+    /// most recently used.
+    ///
+    /// [`ArenaSet::resolve`]: struct.ArenaSet.html#method.resolve
+    pub fn resolve<U, Q: ? Sized>(&self, id: U) -> Result<&Q, Error>
+        where U: Borrow<I>,
+              O: Borrow<Q> {
+        let ix = self.set.index_of(id.borrow())?;
+        self.touch(ix);
+        self.set.resolve(id)
+    }
+
+    /// Turn this back into an unbounded [`ArenaSet`], with the same IDs.
+    ///
+    /// [`ArenaSet`]: struct.ArenaSet.html
+    pub fn into_inner(self) -> ArenaSet<O, I, M> {
+        self.set
+    }
+}
+
+impl<O: StableDeref, I, M> LruArenaSet<O, I, M> {
+    // Mark the item at `ix` as the most recently used.
+    fn touch(&self, ix: usize) {
+        if self.newest.get() != ix {
+            self.unlink(ix);
+            self.push_newest(ix);
+        }
+    }
+
+    fn push_newest(&self, ix: usize) {
+        let newest = self.newest.replace(ix);
+        self.newer[ix].set(!0);
+        self.older[ix].set(newest);
+        if newest == !0 {
+            self.oldest.set(ix);
+        } else {
+            self.newer[newest].set(ix);
+        }
+    }
+
+    fn unlink(&self, ix: usize) {
+        let (newer, older) = (self.newer[ix].get(), self.older[ix].get());
+        if newer == !0 {
+            self.newest.set(older);
+        } else {
+            self.older[newer].set(older);
+        }
+        if older == !0 {
+            self.oldest.set(newer);
+        } else {
+            self.newer[older].set(newer);
+        }
+    }
+}
+
+impl<O, I, M> LruArenaSet<O, I, M>
+where O: StableDeref,
+      O::Target: 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Key = &'static O::Target, Value = I>
+{
+    /// Analogue of [`ArenaSet::intern`], which also marks the item as the
+    /// most recently used.
+    ///
+    /// If the item is new and the set is full, the least recently used item
+    /// is disinterned first, and returned along with its ID. Nothing is
+    /// evicted if the new item can't be given an ID.
+    ///
+    /// [`ArenaSet::intern`]: struct.ArenaSet.html#method.intern
+    pub fn intern<Q>(&mut self, item: Q) -> Result<(I, Option<(I, O)>), Error>
+        where Q: Borrow<O::Target>,
+              O: From<Q> {
+        if let Some(id) = self.set.get_id(item.borrow()) {
+            self.touch(self.set.index_of(&id)?);
+            return Ok((id, None));
+        }
+        if self.set.count() < self.capacity {
+            let id = self.set.intern(item)?;
+            self.link_newest(&id)?;
+            return Ok((id, None));
+        }
+        let oldest = self.oldest.get();
+        let evicted = self.set.to_id(oldest)?;
+        if self.set.sorted.is_some() {
+            // a sorted set places the item between its neighbours rather
+            // than in the evicted slot, so only evict once it's in
+            let id = self.set.intern(item)?;
+            self.link_newest(&id)?;
+            return Ok((id, Some((evicted, self.disintern(evicted)?))));
+        }
+        // the item will take the evicted slot under its next generation;
+        // make sure that ID exists, so a failure can't lose the evicted item
+        let generation = self.set.generations.get(oldest)
+            .map_or(0, |&g| (g + 1) & self.set.packing.generation_mask());
+        I::from_usize(self.set.packing.pack_one(oldest, generation)).ok_or(Error::ToIdFailed)?;
+        let old = self.disintern(evicted)?;
+        let id = self.set.intern(item)?;
+        self.link_newest(&id)?;
+        Ok((id, Some((evicted, old))))
+    }
+
+    fn link_newest(&mut self, id: &I) -> Result<(), Error> {
+        let ix = self.set.index_of(id)?;
+        if ix >= self.newer.len() {
+            self.newer.resize(ix + 1, Cell::new(!0));
+            self.older.resize(ix + 1, Cell::new(!0));
+        }
+        self.push_newest(ix);
+        Ok(())
+    }
+
+    /// Analogue of [`ArenaSet::disintern`].
+    ///
+    /// [`ArenaSet::disintern`]: struct.ArenaSet.html#method.disintern
+    pub fn disintern<U: Borrow<I>>(&mut self, id: U) -> Result<O, Error> {
+        let ix = self.set.index_of(id.borrow())?;
+        self.unlink(ix);
+        self.set.disintern(id)
+    }
+
+    /// Analogue of [`ArenaSet::get_id`]. Doesn't count as a use.
+    ///
+    /// [`ArenaSet::get_id`]: struct.ArenaSet.html#method.get_id
+    #[inline]
+    pub fn get_id(&self, item: &O::Target) -> Option<I> {
+        self.set.get_id(item)
+    }
+
+    /// Analogue of [`ArenaSet::contains`]. Doesn't count as a use.
+    ///
+    /// [`ArenaSet::contains`]: struct.ArenaSet.html#method.contains
+    #[inline]
+    pub fn contains(&self, item: &O::Target) -> bool {
+        self.set.contains(item)
+    }
+}
@@ -0,0 +1,346 @@
+use std::borrow::Borrow;
+use std::fs::File;
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+
+use memmap2::Mmap;
+use num_traits::{Bounded, ToPrimitive, FromPrimitive};
+use stable_deref_trait::StableDeref;
+
+use chunked::ByteSlice;
+use traits::Map;
+
+use super::{ArenaSet, Error, Slot, Packing};
+use super::snapshot::{Blob, FNV_OFFSET_BASIS, fnv1a};
+
+const MAGIC: &[u8; 4] = b"SHWM";
+const VERSION: u16 = 1;
+const HEADER_LEN: usize = 32;
+const SLOT_LEN: usize = 16;
+const VACANT: u64 = !0;
+
+/// A read-only [`ArenaSet`] of `String`s or `Vec<u8>`s, served straight from
+/// a memory-mapped file written by [`write_mapped`].
+///
+/// Nothing is deserialized: [`resolve`] returns references into the mapped
+/// pages, and [`get_id`] probes a hash index stored in the file. The file is
+/// validated when opened, so that every item is in bounds (and valid UTF-8,
+/// for strings), and the index only refers to interned items. IDs are the
+/// same as those of the set that was written.
+///
+/// All integers are little-endian, and the layout is:
+///
+///   * A 32-byte header: the magic bytes `SHWM`, a `u16` version, a `u8`
+///     item kind, a `u8` number of generation bits, and the `u64`s number of
+///     slots, number of index buckets and length of the blob region.
+///   * The slot table: the offset and length of each item as `u64`s, with a
+///     length of `!0` marking vacant slots.
+///   * The generations, as `u64`s, only if there are generation bits.
+///   * The hash index: a power-of-two number of `u64` buckets, each either
+///     zero or one more than the index of a slot, with collisions resolved by
+///     linear probing from the FNV-1a hash of the item.
+///   * The blob region: the bytes of each item, back to back.
+///
+/// ```
+/// use std::fs::File;
+/// use shawshank::MappedArenaSet;
+///
+/// let mut p = shawshank::string_arena_set();
+/// assert_eq!(p.intern("hello"), Ok(0));
+/// assert_eq!(p.intern("world"), Ok(1));
+/// let path = std::env::temp_dir().join("shawshank-mapped-doctest");
+/// p.write_mapped(File::create(&path).unwrap()).unwrap();
+/// // the file isn't modified while mapped
+/// let m = unsafe { MappedArenaSet::<String>::open(&path).unwrap() };
+/// assert_eq!(m.resolve(1), Ok("world"));
+/// assert_eq!(m.get_id("hello"), Some(0));
+/// assert_eq!(m.get_id("again"), None);
+/// # drop(m);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`write_mapped`]: struct.ArenaSet.html#method.write_mapped
+/// [`resolve`]: struct.MappedArenaSet.html#method.resolve
+/// [`get_id`]: struct.MappedArenaSet.html#method.get_id
+pub struct MappedArenaSet<O, I = usize, B = Mmap> {
+    bytes: B,
+    count: usize,
+    slot_count: usize,
+    bucket_count: usize,
+    packing: Packing,
+    // where each region starts
+    generations: usize,
+    buckets: usize,
+    blob: usize,
+    _o: PhantomData<O>,
+    _i: PhantomData<I>,
+}
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: Blob,
+      O::Target: ByteSlice,
+      M: Map
+{
+    /// Write this set in the format read by [`MappedArenaSet`].
+    ///
+    /// [`MappedArenaSet`]: struct.MappedArenaSet.html
+    pub fn write_mapped<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let slot_count = self.interned.len();
+        let bucket_count = (self.map.len() * 2).next_power_of_two();
+        let mut buckets = vec![0u64; bucket_count];
+        let mut blob_len = 0;
+        for (ix, slot) in self.interned.iter().enumerate() {
+            if let Slot::Occupied(ref item) = *slot {
+                let bytes = ByteSlice::as_bytes(item.deref());
+                let mut b = fnv1a(FNV_OFFSET_BASIS, bytes) as usize & (bucket_count - 1);
+                while buckets[b] != 0 {
+                    b = (b + 1) & (bucket_count - 1);
+                }
+                buckets[b] = ix as u64 + 1;
+                blob_len += bytes.len();
+            }
+        }
+        let mut header = [0; HEADER_LEN];
+        header[0..4].copy_from_slice(MAGIC);
+        header[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        header[6] = O::KIND;
+        header[7] = self.packing.generation_bits as u8;
+        header[8..16].copy_from_slice(&(slot_count as u64).to_le_bytes());
+        header[16..24].copy_from_slice(&(bucket_count as u64).to_le_bytes());
+        header[24..32].copy_from_slice(&(blob_len as u64).to_le_bytes());
+        writer.write_all(&header)?;
+        let mut offset = 0u64;
+        for slot in self.interned.iter() {
+            let (start, len) = match *slot {
+                Slot::Occupied(ref item) => (offset, ByteSlice::as_bytes(item.deref()).len() as u64),
+                Slot::Vacant(_) => (0, VACANT),
+            };
+            writer.write_all(&start.to_le_bytes())?;
+            writer.write_all(&len.to_le_bytes())?;
+            if len != VACANT {
+                offset += len;
+            }
+        }
+        if self.packing.generation_bits != 0 {
+            for ix in 0..slot_count {
+                let generation = self.generations.get(ix).cloned().unwrap_or(0);
+                writer.write_all(&(generation as u64).to_le_bytes())?;
+            }
+        }
+        for bucket in buckets {
+            writer.write_all(&bucket.to_le_bytes())?;
+        }
+        for slot in self.interned.iter() {
+            if let Slot::Occupied(ref item) = *slot {
+                writer.write_all(ByteSlice::as_bytes(item.deref()))?;
+            }
+        }
+        writer.flush()
+    }
+}
+
+impl<O, I> MappedArenaSet<O, I>
+where O: Blob,
+      O::Target: ByteSlice,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive
+{
+    /// Map a file written by [`write_mapped`], and validate it.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified, by this or any other process, for as
+    /// long as it's mapped. Otherwise, items could change, or even become
+    /// invalid, after being validated.
+    ///
+    /// [`write_mapped`]: struct.ArenaSet.html#method.write_mapped
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::open(path)?;
+        MappedArenaSet::from_bytes(Mmap::map(&file)?)
+    }
+}
+
+impl<O, I, B> MappedArenaSet<O, I, B>
+where O: Blob,
+      O::Target: ByteSlice,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      B: StableDeref<Target = [u8]>
+{
+    /// Validate bytes written by [`write_mapped`], and serve them as is.
+    ///
+    /// Fails with [`Error::CorruptSnapshot`] if they're truncated, or
+    /// inconsistent in any way that would make resolving unsafe. `B` must be
+    /// `StableDeref`, so that the bytes resolved later are the ones that
+    /// were validated.
+    ///
+    /// ```
+    /// use shawshank::{Error, MappedArenaSet};
+    ///
+    /// let mut p = shawshank::byte_arena_set();
+    /// assert_eq!(p.intern(&[1,2,3][..]), Ok(0));
+    /// let mut buf = Vec::new();
+    /// p.write_mapped(&mut buf).unwrap();
+    /// let m = MappedArenaSet::<Vec<u8>, usize, _>::from_bytes(&buf[..]).unwrap();
+    /// assert_eq!(m.resolve(0), Ok(&[1,2,3][..]));
+    /// // the item's length, now past the end of the blob
+    /// buf[40] = 4;
+    /// assert!(MappedArenaSet::<Vec<u8>, usize, _>::from_bytes(&buf[..]).err() == Some(Error::CorruptSnapshot));
+    /// ```
+    ///
+    /// [`write_mapped`]: struct.ArenaSet.html#method.write_mapped
+    /// [`Error::CorruptSnapshot`]: enum.Error.html#variant.CorruptSnapshot
+    pub fn from_bytes(bytes: B) -> Result<Self, Error> {
+        if bytes.len() < HEADER_LEN || &bytes[0..4] != MAGIC
+            || bytes[4..6] != VERSION.to_le_bytes() || bytes[6] != O::KIND {
+            return Err(Error::CorruptSnapshot);
+        }
+        let packing = Packing::generational::<I>(u32::from(bytes[7])).map_err(|_| Error::CorruptSnapshot)?;
+        let slot_count = read_usize(&bytes, 8)?;
+        let bucket_count = read_usize(&bytes, 16)?;
+        let blob_len = read_usize(&bytes, 24)?;
+        if slot_count > packing.max_idx::<I>()?.saturating_add(1) || !bucket_count.is_power_of_two() {
+            return Err(Error::CorruptSnapshot);
+        }
+        let generation_count = if packing.generation_bits == 0 { 0 } else { slot_count };
+        // find each region, making sure they exactly fill the file
+        let generations = slot_count.checked_mul(SLOT_LEN).and_then(|len| len.checked_add(HEADER_LEN));
+        let buckets = generation_count.checked_mul(8).and_then(|len| len.checked_add(generations?));
+        let blob = bucket_count.checked_mul(8).and_then(|len| len.checked_add(buckets?));
+        let (generations, buckets, blob) = match (generations, buckets, blob) {
+            (Some(g), Some(b), Some(o)) if o.checked_add(blob_len) == Some(bytes.len()) => (g, b, o),
+            _ => return Err(Error::CorruptSnapshot),
+        };
+        let p = MappedArenaSet {
+            bytes,
+            count: 0,
+            slot_count,
+            bucket_count,
+            packing,
+            generations,
+            buckets,
+            blob,
+            _o: PhantomData,
+            _i: PhantomData,
+        };
+        p.validate()
+    }
+
+    /// Get the number of interned items.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Analogue of [`ArenaSet::resolve`], returning a reference into the
+    /// mapped bytes.
+    ///
+    /// Complexity: _O(1)_
+    ///
+    /// [`ArenaSet::resolve`]: struct.ArenaSet.html#method.resolve
+    pub fn resolve<U: Borrow<I>>(&self, id: U) -> Result<&O::Target, Error> {
+        let raw = id.borrow().to_usize().ok_or(Error::FromIdFailed)?;
+        let (ix, generation) = self.packing.unpack(raw)?;
+        if ix < self.slot_count && self.generation(ix) != generation {
+            return Err(Error::StaleId { index: ix });
+        }
+        self.item(ix).ok_or(Error::InvalidId { index: ix })
+    }
+
+    /// Analogue of [`ArenaSet::get_id`], probing the hash index in the file.
+    ///
+    /// [`ArenaSet::get_id`]: struct.ArenaSet.html#method.get_id
+    pub fn get_id(&self, item: &O::Target) -> Option<I> {
+        let bytes = item.as_bytes();
+        let mask = self.bucket_count - 1;
+        let mut b = fnv1a(FNV_OFFSET_BASIS, bytes) as usize & mask;
+        // a corrupted index might have no empty buckets, so don't probe forever
+        for _ in 0..self.bucket_count {
+            let ix = match self.bucket(b) {
+                0 => return None,
+                entry => entry - 1,
+            };
+            if self.item(ix).is_some_and(|candidate| candidate.as_bytes() == bytes) {
+                return I::from_usize(self.packing.pack_one(ix, self.generation(ix)));
+            }
+            b = (b + 1) & mask;
+        }
+        None
+    }
+
+    /// Analogue of [`ArenaSet::contains`].
+    ///
+    /// [`ArenaSet::contains`]: struct.ArenaSet.html#method.contains
+    #[inline]
+    pub fn contains(&self, item: &O::Target) -> bool {
+        self.get_id(item).is_some()
+    }
+
+    // Check everything that `item` and `get_id` rely on, and count the items.
+    fn validate(mut self) -> Result<Self, Error> {
+        let blob_len = self.bytes.len() - self.blob;
+        for ix in 0..self.slot_count {
+            let (start, len) = self.span(ix);
+            if len == VACANT {
+                continue;
+            }
+            let end = start.checked_add(len).filter(|&end| end <= blob_len as u64).ok_or(Error::CorruptSnapshot)?;
+            O::Target::from_bytes(&self.bytes[self.blob + start as usize..self.blob + end as usize])
+                .ok_or(Error::CorruptSnapshot)?;
+            if self.generation(ix) > self.packing.generation_mask() {
+                return Err(Error::CorruptSnapshot);
+            }
+            self.count += 1;
+        }
+        for b in 0..self.bucket_count {
+            match self.bucket(b) {
+                0 => {}
+                entry if entry <= self.slot_count && self.span(entry - 1).1 != VACANT => {}
+                _ => return Err(Error::CorruptSnapshot),
+            }
+        }
+        Ok(self)
+    }
+
+    // invariant: the spans of occupied slots were validated.
+    fn item(&self, ix: usize) -> Option<&O::Target> {
+        if ix >= self.slot_count {
+            return None;
+        }
+        match self.span(ix) {
+            (_, VACANT) => None,
+            (start, len) => {
+                let start = self.blob + start as usize;
+                let bytes = &self.bytes[start..start + len as usize];
+                Some(unsafe { O::Target::from_bytes_unchecked(bytes) })
+            }
+        }
+    }
+
+    fn span(&self, ix: usize) -> (u64, u64) {
+        let pos = HEADER_LEN + ix * SLOT_LEN;
+        (read_u64(&self.bytes, pos), read_u64(&self.bytes, pos + 8))
+    }
+
+    fn generation(&self, ix: usize) -> usize {
+        if self.packing.generation_bits == 0 {
+            0
+        } else {
+            read_u64(&self.bytes, self.generations + ix * 8) as usize
+        }
+    }
+
+    fn bucket(&self, b: usize) -> usize {
+        read_u64(&self.bytes, self.buckets + b * 8) as usize
+    }
+}
+
+fn read_u64(bytes: &[u8], pos: usize) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(&bytes[pos..pos + 8]);
+    u64::from_le_bytes(buf)
+}
+
+fn read_usize(bytes: &[u8], pos: usize) -> Result<usize, Error> {
+    read_u64(bytes, pos).to_usize().ok_or(Error::CorruptSnapshot)
+}
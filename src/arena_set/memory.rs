@@ -0,0 +1,82 @@
+use std::mem;
+use std::sync::atomic::AtomicUsize;
+
+use stable_deref_trait::StableDeref;
+
+use traits::{Map, HeapSize};
+
+use super::{ArenaSet, Slot};
+
+/// A breakdown of the memory used by an [`ArenaSet`], in bytes, from
+/// [`memory_usage`].
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`memory_usage`]: struct.ArenaSet.html#method.memory_usage
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Default)]
+pub struct MemoryUsage {
+    /// The slot table, including its spare capacity.
+    pub slots: usize,
+
+    /// The part of `slots` taken up by vacant slots, which are wasted until
+    /// reused or [shrunk].
+    ///
+    /// [shrunk]: struct.ArenaSet.html#method.shrink
+    pub vacant: usize,
+
+    /// An estimate of the map's own allocations, from
+    /// [`Map::estimated_heap_size`].
+    ///
+    /// [`Map::estimated_heap_size`]: trait.Map.html#method.estimated_heap_size
+    pub map: usize,
+
+    /// The heap owned by the interned items, from [`HeapSize`].
+    ///
+    /// [`HeapSize`]: trait.HeapSize.html
+    pub items: usize,
+
+    /// Per-slot generations, reference counts and hit counts, if enabled.
+    pub metadata: usize,
+}
+
+impl MemoryUsage {
+    /// Get the total number of bytes on the heap.
+    pub fn total(&self) -> usize {
+        self.slots + self.map + self.items + self.metadata
+    }
+}
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref + HeapSize,
+      M: Map
+{
+    /// Measure the memory used by this set on the heap.
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set();
+    /// assert_eq!(p.intern(String::with_capacity(100)), Ok(0));
+    /// assert_eq!(p.intern("hello"), Ok(1));
+    /// p.disintern(0).unwrap();
+    /// let usage = p.memory_usage();
+    /// assert_eq!(usage.items, 5);
+    /// assert!(usage.vacant > 0 && usage.vacant < usage.slots);
+    /// assert!(usage.map > 0);
+    /// ```
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let slot_size = mem::size_of::<Slot<O>>();
+        let mut usage = MemoryUsage {
+            slots: self.interned.capacity() * slot_size,
+            map: self.map.estimated_heap_size(),
+            metadata: self.generations.capacity() * mem::size_of::<usize>()
+                + self.refcounts.as_ref().map_or(0, |refcounts| refcounts.capacity() * mem::size_of::<usize>())
+                + self.hits.as_ref().map_or(0, |hits| hits.capacity() * mem::size_of::<AtomicUsize>()),
+            ..MemoryUsage::default()
+        };
+        for slot in self.interned.iter() {
+            match *slot {
+                Slot::Occupied(ref item) => usage.items += item.heap_size(),
+                Slot::Vacant(_) => usage.vacant += slot_size,
+            }
+        }
+        usage
+    }
+}
@@ -0,0 +1,336 @@
+use std::borrow::Borrow;
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use num_traits::{Bounded, ToPrimitive, FromPrimitive};
+use stable_deref_trait::StableDeref;
+
+use traits::{Map, Interner};
+
+use super::{ArenaSet, StadiumSet, Error, Slot};
+
+impl<O: StableDeref, I, M> ArenaSet<O, I, M> {
+    /// Turn on reference counting.
+    ///
+    /// Every call to [`intern`] then counts a reference to the item, even if
+    /// it was already interned, and [`release`] gives one back. The item is
+    /// disinterned once its last reference is released. Items that are
+    /// already interned start with a single reference.
+    ///
+    /// Counts aren't preserved by [freezing], snapshots or serialization.
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set().refcounted();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.refcount(0), Ok(2));
+    /// assert_eq!(p.release(0), Ok(None));
+    /// assert_eq!(p.release(0), Ok(Some("hello".into())));
+    /// assert!(!p.contains("hello"));
+    /// ```
+    ///
+    /// [`intern`]: struct.ArenaSet.html#method.intern
+    /// [`release`]: struct.ArenaSet.html#method.release
+    /// [freezing]: struct.ArenaSet.html#method.freeze
+    pub fn refcounted(mut self) -> Self {
+        if self.refcounts.is_none() {
+            self.refcounts = Some(self.interned.iter().map(|slot| match *slot {
+                Slot::Occupied(_) => 1,
+                Slot::Vacant(_) => 0,
+            }).collect());
+        }
+        self
+    }
+
+    /// Check whether references to items are counted.
+    #[inline]
+    pub fn is_refcounted(&self) -> bool {
+        self.refcounts.is_some()
+    }
+}
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map
+{
+    /// Count another reference to an interned item, as [`intern`] would.
+    ///
+    /// Does nothing if the set isn't [refcounted], other than checking the ID.
+    ///
+    /// [`intern`]: struct.ArenaSet.html#method.intern
+    /// [refcounted]: struct.ArenaSet.html#method.refcounted
+    pub fn acquire<U: Borrow<I>>(&mut self, id: U) -> Result<(), Error> {
+        let ix = self.index_of(id.borrow())?;
+        self.add_refs(ix, 1);
+        Ok(())
+    }
+
+    /// Get the number of references to an interned item.
+    ///
+    /// This is always one if the set isn't [refcounted].
+    ///
+    /// [refcounted]: struct.ArenaSet.html#method.refcounted
+    pub fn refcount<U: Borrow<I>>(&self, id: U) -> Result<usize, Error> {
+        let ix = self.index_of(id.borrow())?;
+        // invariant: occupied slots always have a count.
+        Ok(self.refcounts.as_ref().map_or(1, |refcounts| refcounts[ix]))
+    }
+
+    // Give back a reference, returning whether it was the last.
+    fn unref(&mut self, id: &I) -> Result<bool, Error> {
+        let ix = self.index_of(id)?;
+        match self.refcounts {
+            // invariant: occupied slots always have a count of at least one.
+            Some(ref mut refcounts) => {
+                refcounts[ix] -= 1;
+                Ok(refcounts[ix] == 0)
+            }
+            None => Ok(true),
+        }
+    }
+}
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      O::Target: 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Key = &'static O::Target, Value = I>
+{
+    /// Give back a reference to an interned item, and [`disintern`] it if it
+    /// was the last. In that case, the item is returned.
+    ///
+    /// If the set isn't [refcounted], every item has a single reference, so
+    /// this always disinterns.
+    ///
+    /// [`disintern`]: struct.ArenaSet.html#method.disintern
+    /// [refcounted]: struct.ArenaSet.html#method.refcounted
+    pub fn release<U: Borrow<I>>(&mut self, id: U) -> Result<Option<O>, Error> {
+        if self.unref(id.borrow())? {
+            self.disintern(id).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<O, R, I, M> StadiumSet<O, R, I, M>
+where O: StableDeref<Target = R>,
+      R: 'static + StableDeref,
+      I: Copy + ToPrimitive + FromPrimitive + Bounded,
+      M: Map<Key = &'static < R as Deref >::Target, Value = I>
+{
+    /// Analogue of [`refcounted`].
+    ///
+    /// ```
+    /// let mut p = shawshank::byte_stadium_set().refcounted();
+    /// assert_eq!(p.intern(&[1,2,3][..]), Ok(0));
+    /// assert_eq!(p.intern(vec![1,2,3]), Ok(0));
+    /// assert_eq!(p.release(0), Ok(None));
+    /// assert!(p.release(0).unwrap().is_some());
+    /// ```
+    ///
+    /// [`refcounted`]: struct.ArenaSet.html#method.refcounted
+    pub fn refcounted(self) -> Self {
+        StadiumSet(self.0.refcounted())
+    }
+
+    /// Analogue of [`acquire`].
+    ///
+    /// [`acquire`]: struct.ArenaSet.html#method.acquire
+    #[inline]
+    pub fn acquire<U: Borrow<I>>(&mut self, id: U) -> Result<(), Error> {
+        self.0.acquire(id)
+    }
+
+    /// Analogue of [`refcount`].
+    ///
+    /// [`refcount`]: struct.ArenaSet.html#method.refcount
+    #[inline]
+    pub fn refcount<U: Borrow<I>>(&self, id: U) -> Result<usize, Error> {
+        self.0.refcount(id)
+    }
+
+    /// Analogue of [`release`].
+    ///
+    /// [`release`]: struct.ArenaSet.html#method.release
+    pub fn release<U: Borrow<I>>(&mut self, id: U) -> Result<Option<O>, Error> {
+        if self.0.unref(id.borrow())? {
+            self.disintern(id).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// A counted reference to an item in a shared, [refcounted] and
+/// [generational] [`ArenaSet`], which is released when dropped.
+///
+/// Cloning a `Symbol` counts another reference, so the item stays interned
+/// until every clone is gone.
+///
+/// ```
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+/// use shawshank::Symbol;
+///
+/// let p = Rc::new(RefCell::new(shawshank::builder::<String>().generational(8).refcounted().hash().unwrap()));
+/// let hello = Symbol::new(&p, "hello").unwrap();
+/// let again = hello.clone();
+/// assert_eq!(&*hello.resolve(), "hello");
+/// assert_eq!(p.borrow().refcount(hello.id()), Ok(2));
+/// drop(hello);
+/// assert!(p.borrow().contains("hello"));
+/// drop(again);
+/// assert!(!p.borrow().contains("hello"));
+/// ```
+///
+/// A `Symbol` only holds the ID of its item, which goes stale when the set
+/// is renumbered, e.g. by [`shrink`]. The symbol then no longer resolves,
+/// and dropping it leaks its reference rather than releasing whichever item
+/// took over the ID:
+///
+/// ```
+/// use std::cell::RefCell;
+/// use std::collections::BTreeMap;
+/// use std::rc::Rc;
+/// use shawshank::Symbol;
+///
+/// let p = Rc::new(RefCell::new(shawshank::builder::<String>().generational(8).refcounted().hash().unwrap()));
+/// let a = Symbol::new(&p, "a").unwrap();
+/// let b = Symbol::new(&p, "b").unwrap();
+/// drop(a);
+/// let _: BTreeMap<_, _> = p.borrow_mut().shrink();
+/// let c = Symbol::new(&p, "c").unwrap();
+/// drop(b);
+/// assert!(p.borrow().contains("b"));
+/// assert!(p.borrow().contains("c"));
+/// assert_eq!(&*c.resolve(), "c");
+/// ```
+///
+/// # Panics
+///
+/// Cloning a `Symbol` panics if the set is borrowed at the time, as does
+/// resolving it while the set is mutably borrowed. Dropping one never
+/// panics: if the set is borrowed, the reference is leaked instead, and the
+/// item stays interned.
+///
+/// [refcounted]: struct.ArenaSet.html#method.refcounted
+/// [generational]: struct.ArenaSet.html#generational-ids
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`shrink`]: struct.ArenaSet.html#method.shrink
+pub struct Symbol<O, I = usize, M = HashMap<&'static < O as Deref >::Target, I>>
+where O: StableDeref,
+      O::Target: 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Key = &'static O::Target, Value = I>
+{
+    set: Rc<RefCell<ArenaSet<O, I, M>>>,
+    id: I,
+}
+
+impl<O, I, M> Symbol<O, I, M>
+where O: StableDeref,
+      O::Target: 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Key = &'static O::Target, Value = I>
+{
+    /// Intern an item into a shared set, holding a reference to it.
+    ///
+    /// Fails with [`Error::Unsupported`] if the set isn't [refcounted], since
+    /// dropping the symbol would then disintern the item regardless of any
+    /// other holders; or if it isn't [generational], since the symbol could
+    /// then release another item once the set is renumbered.
+    ///
+    /// ```
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use shawshank::Symbol;
+    ///
+    /// let p = Rc::new(RefCell::new(shawshank::string_arena_set()));
+    /// assert!(Symbol::new(&p, "hello").is_err());
+    /// assert!(!p.borrow().contains("hello"));
+    ///
+    /// let q = Rc::new(RefCell::new(shawshank::string_arena_set().refcounted()));
+    /// assert!(Symbol::new(&q, "hello").is_err());
+    /// ```
+    ///
+    /// [`Error::Unsupported`]: enum.Error.html#variant.Unsupported
+    /// [refcounted]: struct.ArenaSet.html#method.refcounted
+    /// [generational]: struct.ArenaSet.html#generational-ids
+    pub fn new<Q>(set: &Rc<RefCell<ArenaSet<O, I, M>>>, item: Q) -> Result<Self, Error>
+        where Q: Borrow<O::Target>,
+              O: From<Q> {
+        let mut p = set.borrow_mut();
+        if !p.is_refcounted() {
+            return Err(Error::Unsupported("symbols need a refcounted set"));
+        }
+        if p.packing.generation_bits == 0 {
+            return Err(Error::Unsupported("symbols need a generational set"));
+        }
+        let id = p.intern(item)?;
+        Ok(Symbol {
+            set: set.clone(),
+            id,
+        })
+    }
+
+    /// Get the ID of the item.
+    #[inline]
+    pub fn id(&self) -> I {
+        self.id
+    }
+
+    /// Get the set the item is interned in.
+    #[inline]
+    pub fn set(&self) -> &Rc<RefCell<ArenaSet<O, I, M>>> {
+        &self.set
+    }
+
+    /// Resolve the item.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the item was disinterned behind the symbol's back, or the
+    /// set was renumbered since.
+    pub fn resolve(&self) -> Ref<'_, O::Target> {
+        Ref::map(RefCell::borrow(&self.set), |p| Interner::resolve(p, self.id).expect("symbol was disinterned"))
+    }
+}
+
+impl<O, I, M> Clone for Symbol<O, I, M>
+where O: StableDeref,
+      O::Target: 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Key = &'static O::Target, Value = I>
+{
+    fn clone(&self) -> Self {
+        // a clone that holds no reference could outlive the item, so this
+        // can't fall back to anything but a panic.
+        match self.set.try_borrow_mut() {
+            // if the item was disinterned behind our back, there's nothing to count
+            Ok(mut p) => { let _ = p.acquire(self.id); }
+            Err(_) => panic!("can't clone a Symbol while its set is borrowed"),
+        }
+        Symbol {
+            set: self.set.clone(),
+            id: self.id,
+        }
+    }
+}
+
+impl<O, I, M> Drop for Symbol<O, I, M>
+where O: StableDeref,
+      O::Target: 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Key = &'static O::Target, Value = I>
+{
+    fn drop(&mut self) {
+        // panicking here could abort during unwinding, so leak instead
+        if let Ok(mut p) = self.set.try_borrow_mut() {
+            let _ = p.release(self.id);
+        }
+    }
+}
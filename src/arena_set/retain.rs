@@ -0,0 +1,233 @@
+use std::ops::Deref;
+
+use num_traits::{Bounded, ToPrimitive, FromPrimitive};
+use stable_deref_trait::StableDeref;
+
+use traits::Map;
+
+use super::{ArenaSet, StadiumSet, Slot};
+
+impl<O: StableDeref, I, M> ArenaSet<O, I, M> {
+    // Drop all slots, which must be vacant, and the free list through them.
+    // Their generations are kept, so that IDs issued for them stay stale.
+    pub(super) fn clear_slots(&mut self) {
+        self.interned.clear();
+        self.head = !0;
+        if let Some(ref mut refcounts) = self.refcounts {
+            refcounts.clear();
+        }
+        if let Some(ref mut hits) = self.hits {
+            hits.clear();
+        }
+    }
+}
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map
+{
+    /// Disintern all items, but keep the allocations for new ones.
+    ///
+    /// ```
+    /// let mut p = shawshank::builder::<String>().generational(8).hash().unwrap();
+    /// let id = p.intern("hello").unwrap();
+    /// p.clear();
+    /// assert_eq!(p.count(), 0);
+    /// assert!(p.resolve::<_, str>(id).is_err());
+    /// ```
+    ///
+    /// Complexity: _O(n)_
+    pub fn clear(&mut self) {
+        // the map only borrows from `interned`, so it must go first.
+        self.map.clear();
+        for ix in 0..self.interned.len() {
+            if let Slot::Occupied(_) = self.interned[ix] {
+                self.bump_generation(ix);
+            }
+        }
+        self.clear_slots();
+    }
+}
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      O::Target: 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Key = &'static O::Target, Value = I>
+{
+    /// Keep only the items for which `f` returns `true`, and disintern the
+    /// rest, regardless of any [reference counts].
+    ///
+    /// The items are visited in ID order. As with [`disintern`], the IDs of
+    /// the remaining items don't change.
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set();
+    /// assert_eq!(p.intern_all(vec!["a", "bb", "c"]), Ok(vec![0, 1, 2]));
+    /// p.retain(|_, item| item.len() == 1);
+    /// assert_eq!(p.values().collect::<Vec<_>>(), vec!["a", "c"]);
+    /// assert_eq!(p.get_id("c"), Some(2));
+    /// ```
+    ///
+    /// Complexity: _O(n + removed * [`M::remove(K)`])_
+    ///
+    /// [reference counts]: struct.ArenaSet.html#method.refcounted
+    /// [`disintern`]: struct.ArenaSet.html#method.disintern
+    /// [`M::remove(K)`]: trait.Map.html#tymethod.remove
+    pub fn retain<F: FnMut(I, &O) -> bool>(&mut self, f: F) {
+        self.retain_with(f, |item: &O| item.deref())
+    }
+
+    /// Disintern all items, yielding them and their IDs in ID order.
+    ///
+    /// Each item is disinterned as it's yielded, so the set is consistent even
+    /// if the iterator is leaked. Dropping the iterator disinterns the rest,
+    /// like [`clear`].
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set();
+    /// assert_eq!(p.intern_all(vec!["hello", "world"]), Ok(vec![0, 1]));
+    /// let items: Vec<_> = p.drain().collect();
+    /// assert_eq!(items, vec![(0, "hello".into()), (1, "world".into())]);
+    /// assert_eq!(p.count(), 0);
+    /// ```
+    ///
+    /// [`clear`]: struct.ArenaSet.html#method.clear
+    pub fn drain(&mut self) -> Drain<'_, O, I, M> {
+        Drain { set: self, ix: 0, key: |item: &O| item.deref() }
+    }
+}
+
+// The logic shared by `ArenaSet` and `StadiumSet`, as in `arena_set.rs`.
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Value = I>
+{
+    fn retain_with<F, K, G>(&mut self, mut f: F, key: G)
+        where F: FnMut(I, &O) -> bool,
+              K: ? Sized + 'static,
+              M: Map<Key = &'static K>,
+              G: Fn(&O) -> &K {
+        for ix in 0..self.interned.len() {
+            let id = match self.interned[ix] {
+                // invariant: the index of an occupied slot was already
+                // converted to an ID when the item was interned.
+                Slot::Occupied(ref item) => match self.to_id(ix) {
+                    Ok(id) if !f(id, item) => id,
+                    _ => continue,
+                },
+                Slot::Vacant(_) => continue,
+            };
+            let _ = self.disintern_with(&id, &key);
+        }
+    }
+}
+
+impl<O, R, I, M> StadiumSet<O, R, I, M>
+where O: StableDeref<Target = R>,
+      R: 'static + StableDeref,
+      I: Copy + ToPrimitive + FromPrimitive + Bounded,
+      M: Map<Key = &'static < R as Deref >::Target, Value = I>
+{
+    /// Analogue of [`retain`].
+    ///
+    /// ```
+    /// let mut p = shawshank::byte_stadium_set();
+    /// assert_eq!(p.intern_all(vec![vec![1], vec![2, 3]]), Ok(vec![0, 1]));
+    /// p.retain(|_, item| item.len() > 1);
+    /// assert_eq!(p.get_id(&[1]), None);
+    /// assert_eq!(p.get_id(&[2, 3]), Some(1));
+    /// ```
+    ///
+    /// [`retain`]: struct.ArenaSet.html#method.retain
+    pub fn retain<F: FnMut(I, &O) -> bool>(&mut self, f: F) {
+        self.0.retain_with(f, |item: &O| item.deref().deref())
+    }
+
+    /// Analogue of [`clear`].
+    ///
+    /// [`clear`]: struct.ArenaSet.html#method.clear
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
+
+    /// Analogue of [`drain`].
+    ///
+    /// [`drain`]: struct.ArenaSet.html#method.drain
+    pub fn drain(&mut self) -> Drain<'_, O, I, M, R::Target> {
+        Drain { set: &mut self.0, ix: 0, key: |item: &O| item.deref().deref() }
+    }
+}
+
+/// Draining iterator over the items of an [`ArenaSet`] and their IDs.
+///
+/// Created by [`ArenaSet::drain`]. `K` is the type under which items are
+/// mapped, which only differs for a [`StadiumSet`].
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`ArenaSet::drain`]: struct.ArenaSet.html#method.drain
+/// [`StadiumSet`]: struct.StadiumSet.html
+pub struct Drain<'a, O, I, M, K = < O as Deref >::Target>
+where O: 'a + StableDeref,
+      I: 'a + Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: 'a + Map<Key = &'static K, Value = I>,
+      K: ? Sized + 'static
+{
+    set: &'a mut ArenaSet<O, I, M>,
+    ix: usize,
+    key: fn(&O) -> &K,
+}
+
+impl<'a, O, I, M, K> Iterator for Drain<'a, O, I, M, K>
+where O: 'a + StableDeref,
+      I: 'a + Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: 'a + Map<Key = &'static K, Value = I>,
+      K: ? Sized + 'static
+{
+    type Item = (I, O);
+
+    fn next(&mut self) -> Option<(I, O)> {
+        while self.ix < self.set.interned.len() {
+            let ix = self.ix;
+            self.ix += 1;
+            if let Slot::Occupied(_) = self.set.interned[ix] {
+                // invariant: see `retain_with`.
+                if let Ok(id) = self.set.to_id(ix) {
+                    if let Ok(item) = self.set.disintern_with(&id, self.key) {
+                        return Some((id, item));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.set.count();
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, O, I, M, K> ExactSizeIterator for Drain<'a, O, I, M, K>
+where O: 'a + StableDeref,
+      I: 'a + Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: 'a + Map<Key = &'static K, Value = I>,
+      K: ? Sized + 'static
+{}
+
+impl<'a, O, I, M, K> Drop for Drain<'a, O, I, M, K>
+where O: 'a + StableDeref,
+      I: 'a + Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: 'a + Map<Key = &'static K, Value = I>,
+      K: ? Sized + 'static
+{
+    fn drop(&mut self) {
+        for _ in &mut *self {}
+        // every slot is vacant now, barring IDs that couldn't be converted
+        if self.set.count() == 0 {
+            self.set.clear_slots();
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::ops::Deref;
+
+use num_traits::{Bounded, ToPrimitive, FromPrimitive};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::Error as DeError;
+use stable_deref_trait::StableDeref;
+
+use traits::NewMap;
+
+use super::{ArenaSet, StadiumSet, Slot, Parts};
+
+// The serialized form of both `ArenaSet` and `StadiumSet`. The map and free
+// list are derived from the slots, so they are rebuilt rather than stored.
+#[derive(Serialize)]
+#[serde(rename = "ArenaSet")]
+struct RawRef<'a, O: 'a> {
+    max_idx: usize,
+    generation_bits: u32,
+    generations: &'a [usize],
+    slots: SlotsRef<'a, O>,
+}
+
+struct SlotsRef<'a, O: 'a>(&'a [Slot<O>]);
+
+impl<'a, O: Serialize> Serialize for SlotsRef<'a, O> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|slot| match *slot {
+            Slot::Occupied(ref item) => Some(item),
+            Slot::Vacant(_) => None,
+        }))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "ArenaSet")]
+struct Raw<O> {
+    max_idx: usize,
+    generation_bits: u32,
+    generations: Vec<usize>,
+    slots: Vec<Option<O>>,
+}
+
+impl<O> From<Raw<O>> for Parts<O> {
+    fn from(raw: Raw<O>) -> Parts<O> {
+        Parts {
+            max_idx: raw.max_idx,
+            generation_bits: raw.generation_bits,
+            generations: raw.generations,
+            slots: raw.slots,
+        }
+    }
+}
+
+/// Serializes the items in ID order, with `null` for vacant slots, along
+/// with enough of the configuration to reproduce the exact same IDs.
+///
+/// ```
+/// extern crate serde_json;
+/// extern crate shawshank;
+///
+/// use shawshank::{ArenaSet, Error};
+///
+/// fn main() {
+///     let mut p = shawshank::string_arena_set();
+///     assert_eq!(p.intern("hello"), Ok(0));
+///     assert_eq!(p.intern("world"), Ok(1));
+///     p.disintern(0).unwrap();
+///     let json = serde_json::to_string(&p).unwrap();
+///     let mut q: ArenaSet<String> = serde_json::from_str(&json).unwrap();
+///     assert_eq!(q.resolve::<_, str>(0), Err(Error::InvalidId { index: 0 }));
+///     assert_eq!(q.resolve(1), Ok("world"));
+///     assert_eq!(q.intern("world"), Ok(1));
+///     assert_eq!(q.intern("again"), Ok(0));
+/// }
+/// ```
+impl<O, I, M> Serialize for ArenaSet<O, I, M>
+where O: StableDeref + Serialize
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RawRef {
+            max_idx: self.max_idx,
+            generation_bits: self.packing.generation_bits,
+            generations: &self.generations,
+            slots: SlotsRef(&self.interned),
+        }.serialize(serializer)
+    }
+}
+
+/// Rebuilds the map so that it refers to the newly deserialized items.
+/// Fails if an item appears more than once, or the data is otherwise
+/// inconsistent with the ID type.
+///
+/// ```
+/// extern crate serde_json;
+/// extern crate shawshank;
+///
+/// use shawshank::ArenaSet;
+///
+/// fn main() {
+///     let json = r#"{"max_idx":255,"generation_bits":0,"generations":[],"slots":["a",null,"a"]}"#;
+///     assert!(serde_json::from_str::<ArenaSet<String, u8>>(json).is_err());
+/// }
+/// ```
+impl<'de, O, I, M> Deserialize<'de> for ArenaSet<O, I, M>
+where O: StableDeref + Deserialize<'de>,
+      O::Target: 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: NewMap<Key = &'static O::Target, Value = I>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = Raw::deserialize(deserializer)?;
+        ArenaSet::from_parts(raw.into(), |item: &O| item.deref()).map_err(D::Error::custom)
+    }
+}
+
+/// Same format as that of [`ArenaSet`].
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+impl<O, R, I, M> Serialize for StadiumSet<O, R, I, M>
+where O: StableDeref<Target = R> + Serialize,
+      R: ? Sized + StableDeref
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+/// Same as that of [`ArenaSet`].
+///
+/// ```
+/// extern crate serde_json;
+/// extern crate shawshank;
+///
+/// use shawshank::StadiumSet;
+///
+/// fn main() {
+///     let mut p = shawshank::builder::<Box<Vec<u8>>>().stadium_set_hash().unwrap();
+///     assert_eq!(p.intern(&[1,2,3][..]), Ok(0));
+///     let json = serde_json::to_string(&p).unwrap();
+///     let q: StadiumSet<Box<Vec<u8>>> = serde_json::from_str(&json).unwrap();
+///     assert_eq!(q.get_id(&[1,2,3]), Some(0));
+/// }
+/// ```
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+impl<'de, O, R, I, M> Deserialize<'de> for StadiumSet<O, R, I, M>
+where O: StableDeref<Target = R> + Deserialize<'de>,
+      R: 'static + StableDeref,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: NewMap<Key = &'static < R as Deref >::Target, Value = I>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = Raw::deserialize(deserializer)?;
+        ArenaSet::from_parts(raw.into(), |item: &O| item.deref().deref())
+            .map(StadiumSet)
+            .map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,257 @@
+use std::io::{self, Read, Write};
+use std::str;
+
+use num_traits::{Bounded, ToPrimitive, FromPrimitive};
+use stable_deref_trait::StableDeref;
+
+use traits::{Map, NewMap};
+
+use super::{ArenaSet, Error, Slot, Parts};
+
+const MAGIC: &[u8; 4] = b"SHWK";
+const VERSION: u16 = 1;
+const HEADER_LEN: usize = 40;
+const VACANT: u64 = !0;
+
+/// Items that can be written to and read from a [snapshot].
+///
+/// [snapshot]: struct.ArenaSet.html#snapshots
+pub trait Blob: StableDeref + Sized {
+    /// Tag stored in the header, so that a snapshot of one type of item isn't
+    /// mistaken for another.
+    const KIND: u8;
+
+    /// View the item as raw bytes.
+    fn as_bytes(&self) -> &[u8];
+
+    /// Recreate an item from raw bytes, or `None` if they aren't valid.
+    fn from_bytes(bytes: &[u8]) -> Option<Self>;
+}
+
+impl Blob for String {
+    const KIND: u8 = 0;
+
+    fn as_bytes(&self) -> &[u8] {
+        str::as_bytes(self)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<String> {
+        str::from_utf8(bytes).ok().map(String::from)
+    }
+}
+
+impl Blob for Vec<u8> {
+    const KIND: u8 = 1;
+
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Vec<u8>> {
+        Some(bytes.to_vec())
+    }
+}
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: Blob,
+      O::Target: 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Key = &'static O::Target, Value = I>
+{
+    /// Write a [snapshot] of this set.
+    ///
+    /// ```
+    /// let mut p = shawshank::string_arena_set();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.intern("world"), Ok(1));
+    /// p.disintern(0).unwrap();
+    /// let mut buf = Vec::new();
+    /// p.write_to(&mut buf).unwrap();
+    /// let q = shawshank::ArenaSet::<String>::read_from(&buf[..]).unwrap();
+    /// assert_eq!(q.resolve::<_, str>(0), Err(shawshank::Error::InvalidId { index: 0 }));
+    /// assert_eq!(q.resolve(1), Ok("world"));
+    /// ```
+    ///
+    /// [snapshot]: struct.ArenaSet.html#snapshots
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = Checksummed::new(writer);
+        let blob_len: usize = self.interned.iter().map(|slot| match *slot {
+            Slot::Occupied(ref item) => item.as_bytes().len(),
+            Slot::Vacant(_) => 0,
+        }).sum();
+        let mut header = [0; HEADER_LEN];
+        header[0..4].copy_from_slice(MAGIC);
+        header[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        header[6] = O::KIND;
+        header[7] = self.packing.generation_bits as u8;
+        header[8..16].copy_from_slice(&(self.max_idx as u64).to_le_bytes());
+        header[16..24].copy_from_slice(&(self.interned.len() as u64).to_le_bytes());
+        header[24..32].copy_from_slice(&(self.generations.len() as u64).to_le_bytes());
+        header[32..40].copy_from_slice(&(blob_len as u64).to_le_bytes());
+        writer.write_all(&header)?;
+        for slot in self.interned.iter() {
+            let len = match *slot {
+                Slot::Occupied(ref item) => item.as_bytes().len() as u64,
+                Slot::Vacant(_) => VACANT,
+            };
+            writer.write_all(&len.to_le_bytes())?;
+        }
+        for &generation in &self.generations {
+            writer.write_all(&(generation as u64).to_le_bytes())?;
+        }
+        for slot in self.interned.iter() {
+            if let Slot::Occupied(ref item) = *slot {
+                writer.write_all(item.as_bytes())?;
+            }
+        }
+        let checksum = writer.checksum;
+        writer.inner.write_all(&checksum.to_le_bytes())
+    }
+
+    /// Read a [snapshot], recreating the set with the same IDs.
+    ///
+    /// This is faster than interning each item again, since there's no need
+    /// to look items up before inserting them, and all memory is reserved up
+    /// front. Fails with [`Error::CorruptSnapshot`] if the snapshot is
+    /// truncated, fails its checksum, or is inconsistent in any other way.
+    ///
+    /// ```
+    /// use shawshank::{ArenaSet, Error};
+    ///
+    /// let mut p = shawshank::byte_arena_set();
+    /// assert_eq!(p.intern(&[1,2,3][..]), Ok(0));
+    /// let mut buf = Vec::new();
+    /// p.write_to(&mut buf).unwrap();
+    /// assert_eq!(ArenaSet::<Vec<u8>>::read_from(&buf[..]).unwrap().get_id(&[1,2,3]), Some(0));
+    /// buf[50] ^= 1;
+    /// assert_eq!(ArenaSet::<Vec<u8>>::read_from(&buf[..]).err(), Some(Error::CorruptSnapshot));
+    /// assert_eq!(ArenaSet::<Vec<u8>>::read_from(&buf[..20]).err(), Some(Error::CorruptSnapshot));
+    /// // strings and bytes don't mix
+    /// assert_eq!(ArenaSet::<String>::read_from(&buf[..]).err(), Some(Error::CorruptSnapshot));
+    /// ```
+    ///
+    /// [snapshot]: struct.ArenaSet.html#snapshots
+    /// [`Error::CorruptSnapshot`]: enum.Error.html#variant.CorruptSnapshot
+    pub fn read_from<R: Read>(reader: R) -> Result<Self, Error>
+        where M: NewMap {
+        let mut reader = Checksummed::new(reader);
+        let mut header = [0; HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        if &header[0..4] != MAGIC || header[4..6] != VERSION.to_le_bytes() || header[6] != O::KIND {
+            return Err(Error::CorruptSnapshot);
+        }
+        let generation_bits = u32::from(header[7]);
+        let max_idx = read_usize(&header[8..16])?;
+        let slot_count = read_usize(&header[16..24])?;
+        let generation_count = read_usize(&header[24..32])?;
+        let blob_len = read_usize(&header[32..40])?;
+        // read the lengths of the items, then the generations, then the items
+        let lengths = read_exactly(&mut reader, slot_count, 8)?;
+        let generations = read_exactly(&mut reader, generation_count, 8)?
+            .chunks(8)
+            .map(read_usize)
+            .collect::<Result<Vec<_>, _>>()?;
+        let blob = read_exactly(&mut reader, blob_len, 1)?;
+        let checksum = reader.checksum;
+        let mut trailer = [0; 8];
+        reader.inner.read_exact(&mut trailer)?;
+        if u64::from_le_bytes(trailer) != checksum {
+            return Err(Error::CorruptSnapshot);
+        }
+        let mut slots = Vec::with_capacity(slot_count);
+        let mut offset = 0;
+        for len in lengths.chunks(8) {
+            let len = read_u64(len);
+            if len == VACANT {
+                slots.push(None);
+                continue;
+            }
+            let end = len.to_usize().and_then(|len| len.checked_add(offset)).ok_or(Error::CorruptSnapshot)?;
+            let item = blob.get(offset..end).and_then(O::from_bytes).ok_or(Error::CorruptSnapshot)?;
+            slots.push(Some(item));
+            offset = end;
+        }
+        if offset != blob.len() {
+            return Err(Error::CorruptSnapshot);
+        }
+        let parts = Parts {
+            max_idx,
+            generation_bits,
+            generations,
+            slots,
+        };
+        ArenaSet::from_parts(parts, |item: &O| item.deref()).map_err(|_| Error::CorruptSnapshot)
+    }
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(bytes);
+    u64::from_le_bytes(buf)
+}
+
+fn read_usize(bytes: &[u8]) -> Result<usize, Error> {
+    read_u64(bytes).to_usize().ok_or(Error::CorruptSnapshot)
+}
+
+// Read `count` elements of `size` bytes each. Memory is only reserved as the
+// data actually arrives, so a corrupted count can't exhaust it.
+fn read_exactly<R: Read>(reader: R, count: usize, size: usize) -> Result<Vec<u8>, Error> {
+    let len = count.checked_mul(size).ok_or(Error::CorruptSnapshot)?;
+    let mut buf = Vec::new();
+    reader.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(Error::CorruptSnapshot);
+    }
+    Ok(buf)
+}
+
+pub(crate) const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+// Continue a 64-bit FNV-1a hash with more bytes.
+pub(crate) fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &b in bytes {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+// Computes the 64-bit FNV-1a hash of all bytes passing through.
+struct Checksummed<T> {
+    inner: T,
+    checksum: u64,
+}
+
+impl<T> Checksummed<T> {
+    fn new(inner: T) -> Self {
+        Checksummed {
+            inner,
+            checksum: FNV_OFFSET_BASIS,
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.checksum = fnv1a(self.checksum, bytes);
+    }
+}
+
+impl<W: Write> Write for Checksummed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> Read for Checksummed<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.update(&buf[..n]);
+        Ok(n)
+    }
+}
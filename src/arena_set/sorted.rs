@@ -0,0 +1,209 @@
+use std::cmp;
+use std::ops::Deref;
+
+use num_traits::{Bounded, ToPrimitive, FromPrimitive};
+use stable_deref_trait::StableDeref;
+
+use traits::{Map, NewMap, OrderedMap};
+
+use super::{ArenaSet, StadiumSet, Error, Slot, Sorted, Neighbors, make_static};
+
+impl<O: StableDeref, I, M> ArenaSet<O, I, M> {
+    /// Check whether IDs are kept in the order of their items.
+    #[inline]
+    pub fn is_sorted(&self) -> bool {
+        self.sorted.is_some()
+    }
+
+    // Turn on sorting, with `neighbors` as described for `Sorted`.
+    fn set_sorted(&mut self, spacing: usize, neighbors: Neighbors<O, I, M>) {
+        assert!(spacing != 0, "the spacing of sorted IDs must be positive");
+        // the generation is above the index, and would scramble the order
+        assert!(self.packing.generation_bits == 0, "generational IDs can't be sorted");
+        assert!(self.interned.iter().all(|slot| match *slot {
+            Slot::Vacant(_) => true,
+            Slot::Occupied(_) => false,
+        }), "only an empty set can be sorted");
+        // generations are kept, so that IDs issued before still go stale; but
+        // no vacant slot may stay linked, or the next item would go there.
+        self.clear_slots();
+        self.sorted = Some(Sorted { spacing, neighbors });
+    }
+}
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      O::Target: 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: OrderedMap<Key = &'static O::Target, Value = I>
+{
+    /// Keep IDs in the order of their items, so that comparing two IDs is the
+    /// same as comparing the items.
+    ///
+    /// Each new item is placed halfway between the items just before and
+    /// after it, or `spacing` past the greatest one, so that later items can
+    /// fit in between. Once there is no free ID left in the right place,
+    /// [`intern`] fails with [`Error::IdOverflow`], and [`sort_ids`] can
+    /// spread the IDs out again. The slots in between are vacant, so memory
+    /// use grows with `spacing`.
+    ///
+    /// Sorting isn't preserved by [freezing], snapshots or serialization.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `spacing` is zero, if the set isn't empty, or if it's
+    /// [generational].
+    ///
+    /// ```
+    /// let mut p = shawshank::builder::<String>().btree().unwrap().sorted(4);
+    /// let b = p.intern("b").unwrap();
+    /// let d = p.intern("d").unwrap();
+    /// let c = p.intern("c").unwrap();
+    /// let a = p.intern("a").unwrap();
+    /// assert!(a < b && b < c && c < d);
+    ///
+    /// let cc = p.intern("cc").unwrap();
+    /// assert!(c < cc && cc < d);
+    /// // nothing fits between "c" and "cc" anymore
+    /// assert_eq!(p.intern("cb"), Err(shawshank::Error::IdOverflow { max_idx: !0 }));
+    /// ```
+    ///
+    /// [`intern`]: struct.ArenaSet.html#method.intern
+    /// [`sort_ids`]: struct.ArenaSet.html#method.sort_ids
+    /// [`Error::IdOverflow`]: enum.Error.html#variant.IdOverflow
+    /// [freezing]: struct.ArenaSet.html#method.freeze
+    /// [generational]: struct.ArenaSet.html#generational-ids
+    pub fn sorted(mut self, spacing: usize) -> Self {
+        self.set_sorted(spacing, |map: &M, item: &O| {
+            let (before, after) = map.neighbors(make_static(item.deref()));
+            (before.cloned(), after.cloned())
+        });
+        self
+    }
+}
+
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      O::Target: 'static + Ord,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Key = &'static O::Target, Value = I>
+{
+    /// Renumber the items in their order, so that comparing two IDs is the
+    /// same as comparing the items. Returns a map from the old IDs to the
+    /// new ones.
+    ///
+    /// Like [`shrink`], this re-uses the IDs of disinterned items. If the
+    /// set is [sorted], the items are spread out again by its spacing, as
+    /// far as the maximum index allows. If the set is [generational], only
+    /// the indices within the IDs are in order.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut p = shawshank::builder::<String>().btree().unwrap();
+    /// assert_eq!(p.intern("world"), Ok(0));
+    /// assert_eq!(p.intern("goodbye"), Ok(1));
+    /// assert_eq!(p.intern("hello"), Ok(2));
+    /// let remap: BTreeMap<_, _> = p.sort_ids();
+    /// assert_eq!(remap, vec![(0, 2), (1, 0), (2, 1)].into_iter().collect());
+    /// assert_eq!(p.resolve(0), Ok("goodbye"));
+    /// ```
+    ///
+    /// Complexity: _O(n log n + n * [`T::insert(K)`] + n * [`M::insert(K, V)`])_
+    ///
+    /// [`shrink`]: struct.ArenaSet.html#method.shrink
+    /// [sorted]: struct.ArenaSet.html#method.sorted
+    /// [generational]: struct.ArenaSet.html#generational-ids
+    /// [`T::insert(K)`]: trait.Map.html#tymethod.insert
+    /// [`M::insert(K, V)`]: trait.Map.html#tymethod.insert
+    pub fn sort_ids<T: NewMap<Key = I, Value = I>>(&mut self) -> T {
+        self.sort_ids_with(|item: &O| item.deref())
+    }
+}
+
+// The logic shared by `ArenaSet` and `StadiumSet`, as in `arena_set.rs`.
+impl<O, I, M> ArenaSet<O, I, M>
+where O: StableDeref,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: Map<Value = I>
+{
+    // Find the slot between the neighbours of `item` in a sorted set.
+    pub(super) fn sorted_slot(&self, sorted: &Sorted<O, I, M>, item: &O) -> Result<usize, Error> {
+        let overflow = Error::IdOverflow { max_idx: self.max_idx };
+        let (before, after) = (sorted.neighbors)(&self.map, item);
+        // the first index past the item before
+        let first = match before {
+            Some(id) => self.index_of(&id)?.checked_add(1).ok_or(overflow)?,
+            None => 0,
+        };
+        match after {
+            // halfway to the item after
+            Some(id) => {
+                let end = self.index_of(&id)?;
+                if first >= end {
+                    return Err(overflow);
+                }
+                Ok(first + (end - first) / 2)
+            }
+            None if first > self.max_idx => Err(overflow),
+            None => Ok(cmp::min(first.saturating_add(sorted.spacing - 1), self.max_idx)),
+        }
+    }
+
+    fn sort_ids_with<T, K, G>(&mut self, key: G) -> T
+        where T: NewMap<Key = I, Value = I>,
+              K: ? Sized + Ord + 'static,
+              M: Map<Key = &'static K>,
+              G: Fn(&O) -> &K {
+        let spacing = self.sorted.as_ref().map_or(1, |sorted| sorted.spacing);
+        self.renumber_with(&key, spacing, |items| items.sort_by(|a, b| key(&a.2).cmp(key(&b.2))))
+    }
+}
+
+impl<O, R, I, M> StadiumSet<O, R, I, M>
+where O: StableDeref<Target = R>,
+      R: ? Sized + 'static + StableDeref,
+      I: Copy + ToPrimitive + FromPrimitive + Bounded,
+      M: OrderedMap<Key = &'static < R as Deref >::Target, Value = I>
+{
+    /// Analogue of [`sorted`].
+    ///
+    /// ```
+    /// let mut p = shawshank::builder::<Box<Vec<u8>>>().stadium_set_btree().unwrap().sorted(8);
+    /// let b = p.intern(vec![2]).unwrap();
+    /// let a = p.intern(vec![1, 2]).unwrap();
+    /// assert!(a < b);
+    /// ```
+    ///
+    /// [`sorted`]: struct.ArenaSet.html#method.sorted
+    pub fn sorted(mut self, spacing: usize) -> Self {
+        self.0.set_sorted(spacing, |map: &M, item: &O| {
+            let (before, after) = map.neighbors(make_static(item.deref().deref()));
+            (before.cloned(), after.cloned())
+        });
+        self
+    }
+}
+
+impl<O, R, I, M> StadiumSet<O, R, I, M>
+where O: StableDeref<Target = R>,
+      R: ? Sized + 'static + StableDeref,
+      R::Target: Ord,
+      I: Copy + ToPrimitive + FromPrimitive + Bounded,
+      M: Map<Key = &'static < R as Deref >::Target, Value = I>
+{
+    /// Analogue of [`is_sorted`].
+    ///
+    /// [`is_sorted`]: struct.ArenaSet.html#method.is_sorted
+    #[inline]
+    pub fn is_sorted(&self) -> bool {
+        self.0.is_sorted()
+    }
+
+    /// Analogue of [`sort_ids`].
+    ///
+    /// [`sort_ids`]: struct.ArenaSet.html#method.sort_ids
+    pub fn sort_ids<T: NewMap<Key = I, Value = I>>(&mut self) -> T {
+        self.0.sort_ids_with(|item: &O| item.deref().deref())
+    }
+}
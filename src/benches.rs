@@ -1,10 +1,14 @@
 use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "fxhash")]
+use fxhash::FxBuildHasher;
+#[cfg(feature = "ahash")]
+use ahash::RandomState as AHashBuildHasher;
 use rand::{thread_rng, Rng};
 use super::*;
 use test::Bencher;
-use traits::Map;
+use traits::NewMap;
 
-fn prefilled<M: Map<Key = &'static str, Value = u32>, F, P>(count: u32, f: F) -> (ArenaSet<String, u32, M>, Vec<P>)
+fn prefilled<M: NewMap<Key = &'static str, Value = u32>, F, P>(count: u32, f: F) -> (ArenaSet<String, u32, M>, Vec<P>)
     where F: Fn(String, u32) -> P {
     let mut rng = thread_rng();
     let mut p = ArenaSet::new().unwrap();
@@ -22,7 +26,7 @@ fn prefilled<M: Map<Key = &'static str, Value = u32>, F, P>(count: u32, f: F) ->
 
 // spoiler: all combinations are just as fast!
 /*
-fn bench_resolve_existing<M: Map<Key = &'static str, Value = u32>>(b: &mut Bencher, count: u32) {
+fn bench_resolve_existing<M: NewMap<Key = &'static str, Value = u32>>(b: &mut Bencher, count: u32) {
     let (p, path) = prefilled::<M, _, _>(count, |_, i| { i });
     let mut it = path.iter().cycle();
     b.iter(|| {
@@ -31,7 +35,7 @@ fn bench_resolve_existing<M: Map<Key = &'static str, Value = u32>>(b: &mut Bench
 }
 */
 
-fn bench_intern_existing<M: Map<Key = &'static str, Value = u32>>(b: &mut Bencher, count: u32) {
+fn bench_intern_existing<M: NewMap<Key = &'static str, Value = u32>>(b: &mut Bencher, count: u32) {
     let (mut p, path) = prefilled::<M, _, _>(count, |s, _| { s });
     let mut it = path.iter().cycle();
     b.iter(|| {
@@ -39,7 +43,7 @@ fn bench_intern_existing<M: Map<Key = &'static str, Value = u32>>(b: &mut Benche
     });
 }
 
-fn bench_intern_disintern<M: Map<Key = &'static str, Value = u32>>(b: &mut Bencher, count: u32) {
+fn bench_intern_disintern<M: NewMap<Key = &'static str, Value = u32>>(b: &mut Bencher, count: u32) {
     let (mut p, path) = prefilled::<M, _, _>(count, |s, i| { (s, i) });
     let (mut strings, mut ids): (Vec<_>, Vec<_>) = path.into_iter().unzip();
     thread_rng().shuffle(&mut strings);
@@ -106,3 +110,13 @@ benchset!(BTreeMap<&'static str, u32>, 1000,
 benchset!(BTreeMap<&'static str, u32>, 10000,
         bench_intern_existing_btree_map_prefilled10000
         bench_intern_disintern_btree_map_prefilled10000);
+
+#[cfg(feature = "fxhash")]
+benchset!(HashMap<&'static str, u32, FxBuildHasher>, 1000,
+        bench_intern_existing_fx_hash_map_prefilled1000
+        bench_intern_disintern_fx_hash_map_prefilled1000);
+
+#[cfg(feature = "ahash")]
+benchset!(HashMap<&'static str, u32, AHashBuildHasher>, 1000,
+        bench_intern_existing_ahash_map_prefilled1000
+        bench_intern_disintern_ahash_map_prefilled1000);
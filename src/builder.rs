@@ -1,12 +1,35 @@
 use std::collections::{BTreeMap, HashMap};
-use std::hash::Hash;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
 use std::marker::PhantomData;
 use std::ops::Deref;
 
 use num_traits::{Bounded, ToPrimitive, FromPrimitive};
 use stable_deref_trait::StableDeref;
 
-use arena_set::{Error, ArenaSet, StadiumSet};
+use arena_set::{Error, ArenaSet, StadiumSet, ConsTable, HashedArenaSet};
+use chunked::{ChunkedSet, ByteSlice};
+use concurrent::ConcurrentArenaSet;
+use traits::{Map, NewMap};
+
+// An `ArenaSet` that uses a `HashMap` with hasher `H`.
+type HashArenaSet<O, I, H> = ArenaSet<O, I, HashMap<&'static < O as Deref >::Target, I, H>>;
+
+// A `StadiumSet` of `O`s inside `O::Target`s that uses a `HashMap` with
+// hasher `H`.
+type HashStadiumSet<O, I, H> = StadiumSet<O, < O as Deref >::Target, I, HashMap<&'static < < O as Deref >::Target as Deref >::Target, I, H>>;
+
+// An `ArenaSet` that uses a `BTreeMap`.
+type BTreeArenaSet<O, I> = ArenaSet<O, I, BTreeMap<&'static < O as Deref >::Target, I>>;
+
+// A `StadiumSet` of `O`s inside `O::Target`s that uses a `BTreeMap`.
+type BTreeStadiumSet<O, I> = StadiumSet<O, < O as Deref >::Target, I, BTreeMap<&'static < < O as Deref >::Target as Deref >::Target, I>>;
+
+// A `ChunkedSet` of `T`s that uses a `BTreeMap`.
+type BTreeChunkedSet<T, I> = ChunkedSet<T, I, BTreeMap<&'static T, I>>;
+
+// A `ConcurrentArenaSet` whose shards use a `BTreeMap`.
+type BTreeConcurrentArenaSet<O, I> = ConcurrentArenaSet<O, I, BTreeMap<&'static < O as Deref >::Target, usize>>;
 
 /// Flexible builder for [`ArenaSet`].
 ///
@@ -27,6 +50,10 @@ use arena_set::{Error, ArenaSet, StadiumSet};
 ///
 /// [`ArenaSet`]: struct.ArenaSet.html
 pub struct Builder<O, I = usize> {
+    generation_bits: u32,
+    refcounted: bool,
+    hit_counted: bool,
+    spacing: Option<usize>,
     _o: PhantomData<O>,
     _i: PhantomData<I>,
 }
@@ -34,10 +61,87 @@ pub struct Builder<O, I = usize> {
 impl<O, I> Builder<O, I> {
     pub fn new() -> Self {
         Builder {
+            generation_bits: 0,
+            refcounted: false,
+            hit_counted: false,
+            spacing: None,
             _o: PhantomData,
             _i: PhantomData,
         }
     }
+
+    /// Reserve the top `generation_bits` bits of each ID for a [generation],
+    /// so that IDs of disinterned items are detected rather than aliased.
+    ///
+    /// ```
+    /// let mut p = shawshank::builder::<String>().generational(16).hash().unwrap();
+    /// let id = p.intern("hello").unwrap();
+    /// p.disintern(id).unwrap();
+    /// assert_eq!(p.resolve::<_, str>(id), Err(shawshank::Error::StaleId { index: 0 }));
+    /// ```
+    ///
+    /// [generation]: struct.ArenaSet.html#generational-ids
+    pub fn generational(mut self, generation_bits: u32) -> Self {
+        self.generation_bits = generation_bits;
+        self
+    }
+
+    /// Count references to each item, so that it's only disinterned once
+    /// every one of them has been [released].
+    ///
+    /// ```
+    /// let mut p = shawshank::builder::<String>().refcounted().btree().unwrap();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.release(0), Ok(None));
+    /// assert_eq!(p.resolve(0), Ok("hello"));
+    /// ```
+    ///
+    /// [released]: struct.ArenaSet.html#method.release
+    pub fn refcounted(mut self) -> Self {
+        self.refcounted = true;
+        self
+    }
+
+    /// Count how often each item is interned or resolved, so that the most
+    /// used ones can be [renumbered] to the smallest IDs.
+    ///
+    /// ```
+    /// let mut p = shawshank::builder::<String>().hit_counted().hash().unwrap();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.hits(0), Ok(1));
+    /// ```
+    ///
+    /// [renumbered]: struct.ArenaSet.html#method.renumber_by_frequency
+    pub fn hit_counted(mut self) -> Self {
+        self.hit_counted = true;
+        self
+    }
+
+    /// Keep IDs in the order of their items, with new items `spacing` apart,
+    /// as [`ArenaSet::sorted`] does. This only applies to [`ArenaSet`]s and
+    /// [`StadiumSet`]s that use a `BTreeMap`, and not to [generational] ones;
+    /// creating any other set fails with [`Error::Unsupported`].
+    ///
+    /// ```
+    /// let mut p = shawshank::builder::<String>().sorted(16).btree().unwrap();
+    /// let world = p.intern("world").unwrap();
+    /// let hello = p.intern("hello").unwrap();
+    /// assert!(hello < world);
+    ///
+    /// assert!(shawshank::builder::<String>().sorted(16).hash().is_err());
+    /// assert!(shawshank::builder::<String>().sorted(16).generational(4).btree().is_err());
+    /// ```
+    ///
+    /// [`ArenaSet::sorted`]: struct.ArenaSet.html#method.sorted
+    /// [`ArenaSet`]: struct.ArenaSet.html
+    /// [`StadiumSet`]: struct.StadiumSet.html
+    /// [generational]: struct.ArenaSet.html#generational-ids
+    /// [`Error::Unsupported`]: enum.Error.html#variant.Unsupported
+    pub fn sorted(mut self, spacing: usize) -> Self {
+        self.spacing = Some(spacing);
+        self
+    }
 }
 
 /// Create a [`Builder`] where the ID is `usize`.
@@ -46,22 +150,178 @@ pub fn builder<O>() -> Builder<O> {
     Builder::<O>::new()
 }
 
+impl<O, I> Builder<O, I> {
+    // Fail if sorting was asked for and can't be done, rather than panicking
+    // or ignoring it; `sortable` is whether the set being built supports it.
+    fn check_sorted(&self, sortable: bool) -> Result<(), Error> {
+        match self.spacing {
+            None => Ok(()),
+            Some(_) if !sortable => Err(Error::Unsupported("only sets that use a BTreeMap can be sorted")),
+            Some(0) => Err(Error::Unsupported("the spacing of sorted IDs must be positive")),
+            Some(_) if self.generation_bits != 0 => Err(Error::Unsupported("generational IDs can't be sorted")),
+            Some(_) => Ok(()),
+        }
+    }
+
+    // Fail if any option was given, for sets that support none of them.
+    fn check_no_options(&self) -> Result<(), Error> {
+        self.check_sorted(false)?;
+        if self.generation_bits != 0 || self.refcounted || self.hit_counted {
+            return Err(Error::Unsupported("only an ArenaSet or StadiumSet can be generational, refcounted or hit-counted"));
+        }
+        Ok(())
+    }
+}
+
 impl<O, I> Builder<O, I>
 where O: StableDeref,
       I: Bounded + ToPrimitive + FromPrimitive
 {
+    // Create an empty `ArenaSet` with all options applied.
+    fn arena_set<M: NewMap>(&self) -> Result<ArenaSet<O, I, M>, Error> {
+        self.arena_set_with(M::new())
+    }
+
+    // Create an empty `ArenaSet` that uses `map`, with all options applied.
+    fn arena_set_with<M: Map>(&self, map: M) -> Result<ArenaSet<O, I, M>, Error> {
+        let p = ArenaSet::generational_with_map(self.generation_bits, map)?;
+        let p = if self.refcounted { p.refcounted() } else { p };
+        Ok(if self.hit_counted { p.hit_counted() } else { p })
+    }
+
     /// Create an empty [`ArenaSet`] that uses a `HashMap`.
     /// [`ArenaSet`]: struct.ArenaSet.html
-    pub fn hash(&self) -> Result<ArenaSet<O, I, HashMap<&'static O::Target, I>>, Error>
+    pub fn hash(&self) -> Result<HashArenaSet<O, I, RandomState>, Error>
         where O::Target: Eq + Hash {
-        ArenaSet::new()
+        self.hash_with()
+    }
+
+    /// Create an empty [`ArenaSet`] that uses a `HashMap` hashing with `H`,
+    /// such as `FxBuildHasher` or `AHashBuildHasher` from the `fxhash` and
+    /// `ahash` features. These are much faster than the default, but don't
+    /// resist collision attacks, so they're only suited to trusted input.
+    ///
+    /// ```
+    /// use std::collections::hash_map::DefaultHasher;
+    /// use std::hash::BuildHasherDefault;
+    ///
+    /// let b = shawshank::builder::<String>();
+    /// let mut p = b.hash_with::<BuildHasherDefault<DefaultHasher>>().unwrap();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// ```
+    ///
+    /// [`ArenaSet`]: struct.ArenaSet.html
+    pub fn hash_with<H>(&self) -> Result<HashArenaSet<O, I, H>, Error>
+        where O::Target: Eq + Hash,
+              H: BuildHasher + Default {
+        self.hash_with_hasher(H::default())
+    }
+
+    /// Create an empty [`ArenaSet`] that uses a `HashMap` hashing with
+    /// `hasher`, e.g. one with a fixed seed.
+    ///
+    /// `H` needn't implement `Default`, but then the map isn't a [`NewMap`],
+    /// so the few methods that create a set from scratch, like
+    /// [`FrozenArenaSet::thaw`], aren't available.
+    ///
+    /// ```
+    /// use std::collections::hash_map::DefaultHasher;
+    /// use std::hash::{BuildHasher, Hasher};
+    ///
+    /// // a hasher that can only be made from a seed
+    /// struct Seeded(u64);
+    ///
+    /// impl BuildHasher for Seeded {
+    ///     type Hasher = DefaultHasher;
+    ///
+    ///     fn build_hasher(&self) -> DefaultHasher {
+    ///         let mut hasher = DefaultHasher::new();
+    ///         hasher.write_u64(self.0);
+    ///         hasher
+    ///     }
+    /// }
+    ///
+    /// let mut p = shawshank::builder::<String>().refcounted().hash_with_hasher(Seeded(42)).unwrap();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.get_id("hello"), Some(0));
+    /// assert_eq!(p.release(0), Ok(None));
+    /// ```
+    ///
+    /// [`ArenaSet`]: struct.ArenaSet.html
+    /// [`NewMap`]: trait.NewMap.html
+    /// [`FrozenArenaSet::thaw`]: struct.FrozenArenaSet.html#method.thaw
+    pub fn hash_with_hasher<H>(&self, hasher: H) -> Result<HashArenaSet<O, I, H>, Error>
+        where O::Target: Eq + Hash,
+              H: BuildHasher {
+        self.check_sorted(false)?;
+        self.arena_set_with(HashMap::with_hasher(hasher))
     }
 
     /// Create an empty [`ArenaSet`] that uses a `BTreeMap`.
     /// [`ArenaSet`]: struct.ArenaSet.html
-    pub fn btree(&self) -> Result<ArenaSet<O, I, BTreeMap<&'static O::Target, I>>, Error>
-        where O::Target: Eq + Ord {
-        ArenaSet::new()
+    pub fn btree(&self) -> Result<BTreeArenaSet<O, I>, Error>
+        where O::Target: Eq + Ord,
+              I: Copy {
+        self.check_sorted(true)?;
+        let p: ArenaSet<O, I, BTreeMap<_, _>> = self.arena_set()?;
+        Ok(match self.spacing {
+            Some(spacing) => p.sorted(spacing),
+            None => p,
+        })
+    }
+
+    /// Create an empty [`HashedArenaSet`], which caches the hash of each item
+    /// instead of using a map.
+    ///
+    /// None of the other options apply to it, so giving any fails with
+    /// [`Error::Unsupported`]. The same goes for the sets created below.
+    ///
+    /// [`HashedArenaSet`]: struct.HashedArenaSet.html
+    /// [`Error::Unsupported`]: enum.Error.html#variant.Unsupported
+    pub fn hashed(&self) -> Result<HashedArenaSet<O, I>, Error>
+        where O::Target: Eq + Hash,
+              I: Copy {
+        self.check_no_options()?;
+        HashedArenaSet::new()
+    }
+
+    /// Create an empty [`ChunkedSet`] of `O::Target`s that uses a `HashMap`.
+    /// [`ChunkedSet`]: struct.ChunkedSet.html
+    pub fn chunked_hash(&self) -> Result<ChunkedSet<O::Target, I>, Error>
+        where O::Target: 'static + ByteSlice + Eq + Hash,
+              I: Copy {
+        self.check_no_options()?;
+        ChunkedSet::new()
+    }
+
+    /// Create an empty [`ChunkedSet`] of `O::Target`s that uses a `BTreeMap`.
+    /// [`ChunkedSet`]: struct.ChunkedSet.html
+    pub fn chunked_btree(&self) -> Result<BTreeChunkedSet<O::Target, I>, Error>
+        where O::Target: 'static + ByteSlice + Eq + Ord,
+              I: Copy {
+        self.check_no_options()?;
+        ChunkedSet::new()
+    }
+
+    /// Create an empty [`ConcurrentArenaSet`] with `shards` shards that use a `HashMap`.
+    /// [`ConcurrentArenaSet`]: struct.ConcurrentArenaSet.html
+    pub fn concurrent_hash(&self, shards: usize) -> Result<ConcurrentArenaSet<O, I>, Error>
+        where O::Target: 'static + Eq + Hash,
+              I: Copy {
+        self.check_no_options()?;
+        ConcurrentArenaSet::new(shards)
+    }
+
+    /// Create an empty [`ConcurrentArenaSet`] with `shards` shards that use a `BTreeMap`.
+    ///
+    /// Items are still distributed across shards by hash.
+    /// [`ConcurrentArenaSet`]: struct.ConcurrentArenaSet.html
+    pub fn concurrent_btree(&self, shards: usize) -> Result<BTreeConcurrentArenaSet<O, I>, Error>
+        where O::Target: 'static + Eq + Hash + Ord,
+              I: Copy {
+        self.check_no_options()?;
+        ConcurrentArenaSet::new(shards)
     }
 }
 
@@ -73,15 +333,35 @@ where O: StableDeref,
 {
     /// Create an empty [`StadiumSet`] that uses a `HashMap`.
     /// [`StadiumSet`]: struct.StadiumSet.html
-    pub fn stadium_set_hash(&self) -> Result<StadiumSet<O, O::Target, I, HashMap<&'static < O::Target as Deref >::Target, I>>, Error>
+    pub fn stadium_set_hash(&self) -> Result<HashStadiumSet<O, I, RandomState>, Error>
         where < O::Target as Deref >::Target: Eq + Hash {
-        ArenaSet::new().map(|p| StadiumSet(p))
+        self.check_sorted(false)?;
+        self.arena_set().map(|p| StadiumSet(p))
     }
 
     /// Create an empty [`StadiumSet`] that uses a `BTreeMap`.
     /// [`StadiumSet`]: struct.StadiumSet.html
-    pub fn stadium_set_btree(&self) -> Result<StadiumSet<O, O::Target, I, BTreeMap<&'static < O::Target as Deref >::Target, I>>, Error>
-        where < O::Target as Deref >::Target: Eq + Ord {
-        ArenaSet::new().map(|p| StadiumSet(p))
+    pub fn stadium_set_btree(&self) -> Result<BTreeStadiumSet<O, I>, Error>
+        where < O::Target as Deref >::Target: Eq + Ord,
+              I: Copy {
+        self.check_sorted(true)?;
+        let p: StadiumSet<O, O::Target, I, BTreeMap<_, _>> = StadiumSet(self.arena_set()?);
+        Ok(match self.spacing {
+            Some(spacing) => p.sorted(spacing),
+            None => p,
+        })
+    }
+}
+
+impl<T, I> Builder<T, I>
+where T: Copy,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive
+{
+    /// Create an empty [`ConsTable`], which indexes values by their hashes.
+    /// [`ConsTable`]: struct.ConsTable.html
+    pub fn cons_hash(&self) -> Result<ConsTable<T, I>, Error>
+        where T: Eq + Hash {
+        self.check_no_options()?;
+        ConsTable::new()
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,252 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::str;
+
+use num_traits::{Bounded, ToPrimitive, FromPrimitive};
+
+use arena_set::{Error, make_static};
+use traits::NewMap;
+
+/// Unsized types that are stored as plain bytes by a [`ChunkedSet`].
+///
+/// # Safety
+///
+/// `from_bytes_unchecked` must accept any bytes returned by `as_bytes`, or
+/// accepted by `from_bytes`.
+///
+/// [`ChunkedSet`]: struct.ChunkedSet.html
+pub unsafe trait ByteSlice {
+    /// View the item as raw bytes.
+    fn as_bytes(&self) -> &[u8];
+
+    /// View raw bytes as an item, or `None` if they aren't valid.
+    fn from_bytes(bytes: &[u8]) -> Option<&Self>;
+
+    /// View raw bytes as an item.
+    ///
+    /// # Safety
+    ///
+    /// `bytes` must have been returned by `as_bytes`, or accepted by
+    /// `from_bytes`.
+    unsafe fn from_bytes_unchecked(bytes: &[u8]) -> &Self;
+}
+
+unsafe impl ByteSlice for str {
+    fn as_bytes(&self) -> &[u8] {
+        str::as_bytes(self)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<&str> {
+        str::from_utf8(bytes).ok()
+    }
+
+    unsafe fn from_bytes_unchecked(bytes: &[u8]) -> &str {
+        str::from_utf8_unchecked(bytes)
+    }
+}
+
+unsafe impl ByteSlice for [u8] {
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<&[u8]> {
+        Some(bytes)
+    }
+
+    unsafe fn from_bytes_unchecked(bytes: &[u8]) -> &[u8] {
+        bytes
+    }
+}
+
+/// An internment structure that packs all items into large, append-only
+/// chunks of memory, rather than allocating each one separately.
+///
+/// Each slot only records where its item lives, as (chunk, offset, length).
+/// A chunk is never reallocated once created, so the map can borrow from it
+/// exactly as [`ArenaSet`]'s map borrows from the owned items. Items longer
+/// than the chunk size get a chunk of their own.
+///
+/// The price is that items can't be disinterned, since the chunks are never
+/// compacted.
+///
+/// ```
+/// let mut p = shawshank::builder::<String>().chunked_hash().unwrap();
+/// assert_eq!(p.intern("hello"), Ok(0));
+/// assert_eq!(p.intern("world"), Ok(1));
+/// assert_eq!(p.intern(String::from("hello")), Ok(0));
+/// assert_eq!(p.resolve(1), Ok("world"));
+/// assert_eq!(p.chunks(), 1);
+/// ```
+///
+/// # Type Parameters
+///
+///   * `T`: The type of interned items (e.g. `str`, `[u8]`).
+///   * `I`: The "ID" type to uniquely resolve interned items.
+///   * `M`: The type used to [`Map`] `T`s to `I`s.
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`Map`]: trait.Map.html
+pub struct ChunkedSet<T: ? Sized + 'static, I = usize, M = HashMap<&'static T, I>> {
+    map: M,
+    chunks: Vec<Vec<u8>>,
+    spans: Vec<Span>,
+    chunk_size: usize,
+    offset: usize,
+    max_idx: usize,
+    _t: PhantomData<&'static T>,
+    _i: PhantomData<I>,
+}
+
+// Where an item lives among the chunks.
+#[derive(Clone, Copy)]
+struct Span {
+    chunk: usize,
+    offset: usize,
+    len: usize,
+}
+
+/// The default size of each chunk of a [`ChunkedSet`], in bytes.
+///
+/// [`ChunkedSet`]: struct.ChunkedSet.html
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+impl<T, I, M> ChunkedSet<T, I, M>
+where T: ? Sized + ByteSlice + 'static,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: NewMap<Key = &'static T, Value = I>
+{
+    /// Create a new, empty ChunkedSet with chunks of [`DEFAULT_CHUNK_SIZE`] bytes.
+    ///
+    /// [`DEFAULT_CHUNK_SIZE`]: constant.DEFAULT_CHUNK_SIZE.html
+    #[inline]
+    pub fn new() -> Result<Self, Error> {
+        Self::with_chunk_size(DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Create a new, empty ChunkedSet with chunks of `chunk_size` bytes.
+    pub fn with_chunk_size(chunk_size: usize) -> Result<Self, Error> {
+        let offset = I::min_value().to_usize().ok_or(Error::FromIdFailed)?;
+        Ok(ChunkedSet {
+            map: M::new(),
+            chunks: Vec::new(),
+            spans: Vec::new(),
+            chunk_size,
+            offset,
+            max_idx: I::max_value().to_usize().ok_or(Error::FromIdFailed)? - offset,
+            _t: PhantomData,
+            _i: PhantomData,
+        })
+    }
+
+    /// Get the number of interned items.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Get the number of chunks allocated so far.
+    #[inline]
+    pub fn chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Analogue of [`ArenaSet::intern`]. The item's bytes are copied into the
+    /// current chunk, unless they don't fit, in which case a new one is
+    /// allocated.
+    ///
+    /// ```
+    /// let mut p = shawshank::builder::<Vec<u8>>().chunked_hash().unwrap();
+    /// assert_eq!(p.intern(&[1,2,3][..]), Ok(0));
+    /// assert_eq!(p.intern(vec![1,2,3]), Ok(0));
+    /// ```
+    ///
+    /// Complexity: _O(max([`M::get(K)`], [`M::insert(K,V)`]))_
+    ///
+    /// [`ArenaSet::intern`]: struct.ArenaSet.html#method.intern
+    /// [`M::get(K)`]: trait.Map.html#tymethod.get
+    /// [`M::insert(K,V)`]: trait.Map.html#tymethod.insert
+    pub fn intern<Q: Borrow<T>>(&mut self, item: Q) -> Result<I, Error> {
+        let item = item.borrow();
+        // fast case: item already interned
+        if let Some(id) = self.map.get(make_static(item)) {
+            return Ok(*id);
+        }
+        // don't let IDs overflow
+        let ix = self.spans.len();
+        if ix != 0 && ix - 1 == self.max_idx {
+            return Err(Error::IdOverflow { max_idx: self.max_idx });
+        }
+        let id = I::from_usize(self.offset + ix).ok_or(Error::ToIdFailed)?;
+        let span = self.append(item.as_bytes());
+        self.spans.push(span);
+        let reference = make_static(self.get(span));
+        self.map.insert(reference, id);
+        Ok(id)
+    }
+
+    /// Analogue of [`ArenaSet::get_id`].
+    ///
+    /// ```
+    /// let mut p = shawshank::builder::<String>().chunked_hash().unwrap();
+    /// assert_eq!(p.intern("hello"), Ok(0));
+    /// assert_eq!(p.get_id("hello"), Some(0));
+    /// assert_eq!(p.get_id("world"), None);
+    /// ```
+    ///
+    /// [`ArenaSet::get_id`]: struct.ArenaSet.html#method.get_id
+    #[inline]
+    pub fn get_id(&self, item: &T) -> Option<I> {
+        self.map.get(make_static(item)).cloned()
+    }
+
+    /// Analogue of [`ArenaSet::contains`].
+    ///
+    /// [`ArenaSet::contains`]: struct.ArenaSet.html#method.contains
+    #[inline]
+    pub fn contains(&self, item: &T) -> bool {
+        self.get_id(item).is_some()
+    }
+
+    /// Analogue of [`ArenaSet::resolve`].
+    ///
+    /// Complexity: _O(1)_
+    ///
+    /// [`ArenaSet::resolve`]: struct.ArenaSet.html#method.resolve
+    #[inline]
+    pub fn resolve<U: Borrow<I>>(&self, id: U) -> Result<&T, Error> {
+        let ix = id.borrow().to_usize()
+            .and_then(|raw| raw.checked_sub(self.offset))
+            .ok_or(Error::FromIdFailed)?;
+        self.spans.get(ix).map(|&span| self.get(span)).ok_or(Error::InvalidId { index: ix })
+    }
+
+    // Copy bytes into a chunk with enough room left.
+    fn append(&mut self, bytes: &[u8]) -> Span {
+        if bytes.is_empty() {
+            return Span { chunk: !0, offset: 0, len: 0 };
+        }
+        let fits = self.chunks.last().is_some_and(|chunk| chunk.capacity() - chunk.len() >= bytes.len());
+        if !fits {
+            // invariant: pushing to a chunk never exceeds its capacity, so
+            // its contents never move.
+            self.chunks.push(Vec::with_capacity(bytes.len().max(self.chunk_size)));
+        }
+        let chunk = self.chunks.len() - 1;
+        let buf = &mut self.chunks[chunk];
+        let offset = buf.len();
+        buf.extend_from_slice(bytes);
+        Span { chunk, offset, len: bytes.len() }
+    }
+
+    fn get(&self, span: Span) -> &T {
+        let bytes = if span.len == 0 {
+            &[]
+        } else {
+            &self.chunks[span.chunk][span.offset..span.offset + span.len]
+        };
+        // invariant: the bytes were copied from `as_bytes`.
+        unsafe { T::from_bytes_unchecked(bytes) }
+    }
+}
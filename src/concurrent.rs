@@ -0,0 +1,195 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::RwLock;
+
+use num_traits::{Bounded, ToPrimitive, FromPrimitive};
+use stable_deref_trait::StableDeref;
+
+use arena_set::{ArenaSet, Error};
+use traits::NewMap;
+
+/// A thread-safe [`ArenaSet`] that can intern through a shared reference.
+///
+/// Items are distributed by hash across several shards, each an `ArenaSet`
+/// behind its own `RwLock`, so that threads interning different items rarely
+/// contend. The shard is encoded in the low "digit" of each ID: an item at
+/// index `ix` of shard `s` out of `n` gets the ID `ix * n + s`.
+///
+/// Items can't be disinterned, so references returned by [`resolve`] remain
+/// valid for as long as the set is borrowed, without holding any lock.
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let p = Arc::new(shawshank::builder::<String>().concurrent_hash(4).unwrap());
+/// let handles: Vec<_> = (0..4).map(|i| {
+///     let p = p.clone();
+///     thread::spawn(move || {
+///         let shared = p.intern("shared").unwrap();
+///         let own = p.intern(format!("thread {}", i)).unwrap();
+///         (shared, own)
+///     })
+/// }).collect();
+/// let ids: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+/// assert!(ids.iter().all(|&(shared, _)| shared == ids[0].0));
+/// assert_eq!(p.count(), 5);
+/// assert_eq!(p.resolve(ids[2].1), Ok("thread 2"));
+/// ```
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`resolve`]: struct.ConcurrentArenaSet.html#method.resolve
+pub struct ConcurrentArenaSet<O: StableDeref, I = usize, M = HashMap<&'static < O as Deref >::Target, usize>> {
+    shards: Vec<RwLock<ArenaSet<O, usize, M>>>,
+    hasher: RandomState,
+    offset: usize,
+    max_idx: usize,
+    _i: PhantomData<I>,
+}
+
+impl<O, I, M> ConcurrentArenaSet<O, I, M>
+where O: StableDeref,
+      O::Target: 'static + Hash,
+      I: Copy + Bounded + ToPrimitive + FromPrimitive,
+      M: NewMap<Key = &'static O::Target, Value = usize>
+{
+    /// Create a new, empty ConcurrentArenaSet with `shards` shards.
+    ///
+    /// Fails with [`Error::IdOverflow`] if the ID type can't represent at
+    /// least one item per shard.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    ///
+    /// [`Error::IdOverflow`]: enum.Error.html#variant.IdOverflow
+    pub fn new(shards: usize) -> Result<Self, Error> {
+        assert!(shards != 0, "a ConcurrentArenaSet needs at least one shard");
+        let offset = I::min_value().to_usize().ok_or(Error::FromIdFailed)?;
+        let max_idx = I::max_value().to_usize().ok_or(Error::FromIdFailed)? - offset;
+        if shards - 1 > max_idx {
+            return Err(Error::IdOverflow { max_idx });
+        }
+        // every index of every shard must map to an ID within range
+        let shards = (0..shards)
+            .map(|s| ArenaSet::bounded_with_capacity((max_idx - s) / shards, 0).map(RwLock::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ConcurrentArenaSet {
+            shards,
+            hasher: RandomState::new(),
+            offset,
+            max_idx,
+            _i: PhantomData,
+        })
+    }
+
+    /// Get the number of shards.
+    #[inline]
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Get the number of interned items.
+    ///
+    /// Other threads may intern more items while the shards are counted.
+    pub fn count(&self) -> usize {
+        self.shards.iter().map(|shard| shard.read().expect("shard lock poisoned").count()).sum()
+    }
+
+    /// Analogue of [`ArenaSet::intern`] that only needs a shared reference.
+    ///
+    /// The item's shard is read-locked to check whether it is already
+    /// interned, and only write-locked if it isn't.
+    ///
+    /// ```
+    /// let p = shawshank::builder::<String>().concurrent_hash(2).unwrap();
+    /// let id = p.intern("hello").unwrap();
+    /// assert_eq!(p.intern(String::from("hello")), Ok(id));
+    /// ```
+    ///
+    /// [`ArenaSet::intern`]: struct.ArenaSet.html#method.intern
+    pub fn intern<Q>(&self, item: Q) -> Result<I, Error>
+        where Q: Borrow<O::Target>,
+              O: From<Q> {
+        let s = self.shard_of(item.borrow());
+        let shard = &self.shards[s];
+        let existing = shard.read().expect("shard lock poisoned").get_id(item.borrow());
+        let ix = match existing {
+            Some(ix) => ix,
+            // another thread may have interned it in the meantime, but
+            // `intern` will simply find it then.
+            None => shard.write().expect("shard lock poisoned").intern(item).map_err(|err| match err {
+                // report the bound of the whole set, not just the shard
+                Error::IdOverflow { .. } => Error::IdOverflow { max_idx: self.max_idx },
+                err => err,
+            })?,
+        };
+        self.to_id(s, ix)
+    }
+
+    /// Analogue of [`ArenaSet::get_id`].
+    ///
+    /// ```
+    /// let p = shawshank::builder::<String>().concurrent_hash(2).unwrap();
+    /// let id = p.intern("hello").unwrap();
+    /// assert_eq!(p.get_id("hello"), Some(id));
+    /// assert_eq!(p.get_id("world"), None);
+    /// ```
+    ///
+    /// [`ArenaSet::get_id`]: struct.ArenaSet.html#method.get_id
+    pub fn get_id(&self, item: &O::Target) -> Option<I> {
+        let s = self.shard_of(item);
+        let ix = self.shards[s].read().expect("shard lock poisoned").get_id(item);
+        ix.and_then(|ix| self.to_id(s, ix).ok())
+    }
+
+    /// Analogue of [`ArenaSet::contains`].
+    ///
+    /// [`ArenaSet::contains`]: struct.ArenaSet.html#method.contains
+    #[inline]
+    pub fn contains(&self, item: &O::Target) -> bool {
+        self.get_id(item).is_some()
+    }
+
+    /// Analogue of [`ArenaSet::resolve`].
+    ///
+    /// Only the ID's shard is read-locked, and only while the item is looked
+    /// up.
+    ///
+    /// ```
+    /// let p = shawshank::builder::<String>().concurrent_hash(2).unwrap();
+    /// let id = p.intern("hello").unwrap();
+    /// assert_eq!(p.resolve(id), Ok("hello"));
+    /// ```
+    ///
+    /// [`ArenaSet::resolve`]: struct.ArenaSet.html#method.resolve
+    pub fn resolve<U: Borrow<I>>(&self, id: U) -> Result<&O::Target, Error> {
+        let raw = id.borrow().to_usize()
+            .and_then(|raw| raw.checked_sub(self.offset))
+            .ok_or(Error::FromIdFailed)?;
+        let n = self.shards.len();
+        let shard = self.shards[raw % n].read().expect("shard lock poisoned");
+        let item: *const O::Target = shard.resolve::<_, O>(raw / n)
+            .map_err(|err| match err {
+                Error::InvalidId { .. } => Error::InvalidId { index: raw },
+                err => err,
+            })?
+            .deref();
+        // items are never removed from the shards, and `O: StableDeref`, so
+        // the target outlives the lock for as long as `self` is borrowed.
+        Ok(unsafe { &*item })
+    }
+
+    fn shard_of(&self, item: &O::Target) -> usize {
+        (self.hasher.hash_one(item) % self.shards.len() as u64) as usize
+    }
+
+    fn to_id(&self, shard: usize, ix: usize) -> Result<I, Error> {
+        // invariant: the bound of each shard keeps this from overflowing.
+        I::from_usize(self.offset + ix * self.shards.len() + shard).ok_or(Error::ToIdFailed)
+    }
+}
@@ -6,6 +6,14 @@
 
 extern crate num_traits;
 extern crate stable_deref_trait;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "memmap")]
+extern crate memmap2;
+#[cfg(feature = "fxhash")]
+extern crate fxhash;
+#[cfg(feature = "ahash")]
+extern crate ahash;
 
 #[cfg(test)]
 extern crate rand;
@@ -15,6 +23,8 @@ extern crate test;
 
 mod arena_set;
 mod builder;
+mod chunked;
+mod concurrent;
 mod traits;
 mod utility;
 #[macro_use] mod macros;
@@ -23,6 +33,15 @@ mod utility;
 mod benches;
 
 pub use builder::{Builder, builder};
-pub use arena_set::{Error, ArenaSet, StadiumSet};
-pub use traits::Map;
+pub use chunked::{ChunkedSet, ByteSlice, DEFAULT_CHUNK_SIZE};
+pub use concurrent::ConcurrentArenaSet;
+pub use arena_set::{Error, ArenaSet, StadiumSet, ConsTable, FrozenArenaSet, HashedArenaSet, LruArenaSet, MemoryUsage, Symbol, Blob, Iter, Ids, Values, IntoIter, Drain};
+#[cfg(feature = "memmap")]
+pub use arena_set::MappedArenaSet;
+#[cfg(feature = "fxhash")]
+pub use fxhash::FxBuildHasher;
+#[cfg(feature = "ahash")]
+pub use ahash::RandomState as AHashBuildHasher;
+pub use traits::{Map, NewMap, OrderedMap, Interner, HeapSize};
+pub use stable_deref_trait::StableDeref;
 pub use utility::{string_arena_set, byte_arena_set, string_stadium_set, byte_stadium_set};
@@ -29,6 +29,122 @@ macro_rules! custom_intern_id {
         }
     };
     ( $name:ident, $base:ty ) => {
-        custom_intern_id!($name, $base, <$base as ::num::Bounded>::min_value(), <$base as ::num::Bounded>::max_value());
+        custom_intern_id!($name, $base, <$base as ::num_traits::Bounded>::min_value(), <$base as ::num_traits::Bounded>::max_value());
+    };
+}
+
+/// Declares symbols that are pre-interned at constant IDs.
+///
+/// Given a custom ID type from [`custom_intern_id!`], this adds an associated
+/// constant to it for each symbol, numbered in order from the minimum of the
+/// ID type. That minimum follows the type if it isn't zero, and must then be
+/// a constant expression.
+/// It also declares an extension trait for [`Builder`], with a method that
+/// creates a hash-based [`ArenaSet`] already seeded with the symbols at
+/// exactly those IDs. All other options of the `Builder` still apply.
+///
+/// ```
+/// #[macro_use] extern crate shawshank;
+/// extern crate num_traits;
+///
+/// custom_intern_id!(Sym, u32);
+///
+/// intern_symbols!(Sym, Keywords, keywords, {
+///     FN = "fn",
+///     LET = "let",
+///     /// Doc comments are allowed.
+///     MATCH = "match",
+/// });
+///
+/// fn main() {
+///     let mut p = shawshank::Builder::<String, Sym>::new().keywords().unwrap();
+///     assert_eq!(Sym::LET, Sym(1));
+///     assert_eq!(p.resolve(Sym::MATCH), Ok("match"));
+///     assert_eq!(p.intern("fn"), Ok(Sym::FN));
+///     assert_eq!(p.intern("ident"), Ok(Sym(3)));
+/// }
+/// ```
+///
+/// The generated method fails with [`Error::Unsupported`] if a symbol
+/// doesn't get its constant ID, which happens if a symbol is repeated, or the
+/// minimum given doesn't match the ID type's:
+///
+/// ```
+/// #[macro_use] extern crate shawshank;
+/// extern crate num_traits;
+///
+/// custom_intern_id!(Id, u16, 100, 199);
+/// custom_intern_id!(Dup, u16, 100, 199);
+///
+/// intern_symbols!(Id, 100, Symbols, symbols, {
+///     A = "a",
+///     B = "b",
+/// });
+///
+/// intern_symbols!(Dup, 100, Duplicates, duplicates, {
+///     X = "x",
+///     AGAIN = "x",
+/// });
+///
+/// // numbered from zero
+/// intern_symbols!(Id, Unshifted, unshifted, {
+///     C = "c",
+/// });
+///
+/// fn main() {
+///     let mut p = shawshank::Builder::<String, Id>::new().symbols().unwrap();
+///     assert_eq!(Id::B, Id(101));
+///     assert_eq!(p.intern("b"), Ok(Id::B));
+///     assert!(shawshank::Builder::<String, Dup>::new().duplicates().is_err());
+///     assert!(shawshank::Builder::<String, Id>::new().unshifted().is_err());
+/// }
+/// ```
+///
+/// [`custom_intern_id!`]: macro.custom_intern_id.html
+/// [`Builder`]: struct.Builder.html
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`Error::Unsupported`]: enum.Error.html#variant.Unsupported
+#[macro_export]
+macro_rules! intern_symbols {
+    ( $id:ident, $trait_name:ident, $method:ident, { $( $(#[$attr:meta])* $name:ident = $value:expr ),* $(,)* } ) => {
+        intern_symbols!($id, 0, $trait_name, $method, { $( $(#[$attr])* $name = $value ),* });
+    };
+    ( $id:ident, $min:expr, $trait_name:ident, $method:ident, { $( $(#[$attr:meta])* $name:ident = $value:expr ),* $(,)* } ) => {
+        // numbers the symbols without recursing, however many there are
+        #[doc(hidden)]
+        #[allow(non_snake_case)]
+        mod $method {
+            #[allow(non_camel_case_types, dead_code)]
+            pub enum Offset { $( $name ),* }
+        }
+
+        impl $id {
+            $(
+                $(#[$attr])*
+                #[allow(dead_code)]
+                const $name: $id = $id((($min) as i128 + $method::Offset::$name as i128) as _);
+            )*
+        }
+
+        trait $trait_name<O: $crate::StableDeref> {
+            /// Create an empty `ArenaSet` that uses a `HashMap`, seeded with
+            /// the symbols.
+            fn $method(&self) -> Result<$crate::ArenaSet<O, $id>, $crate::Error>;
+        }
+
+        impl<O> $trait_name<O> for $crate::Builder<O, $id>
+        where O: $crate::StableDeref<Target = str> + From<&'static str>
+        {
+            fn $method(&self) -> Result<$crate::ArenaSet<O, $id>, $crate::Error> {
+                let mut p = self.hash()?;
+                $(
+                    if p.intern($value)? != $id::$name {
+                        return Err($crate::Error::Unsupported(
+                            concat!("symbol `", stringify!($name), "` didn't get its constant ID")));
+                    }
+                )*
+                Ok(p)
+            }
+        }
     };
 }
@@ -1,32 +1,123 @@
+use std::borrow::Borrow;
 use std::collections::{BTreeMap, HashMap};
 use std::hash::{Hash, BuildHasher};
+use std::mem;
+use std::ops::Bound;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use arena_set::Error;
 
 /// The interface for the key-value map internal to an [`ArenaSet`].
 ///
+/// Creating a map is left to [`NewMap`], so that a map which can't be made
+/// from nothing, such as a `HashMap` with a hasher that has no `Default`,
+/// can still be given to a set by [`ArenaSet::with_map`].
+///
 /// The Entry API is not supported, because it can't be used as is, anyway:
 /// the reference passed as a key to `entry(K)` would be to something outside
 /// the arena_set, which we absolutely don't want to store in the map. The Entry
 /// API would have to be extended to allow changing the key before insertion.
 ///
+/// # Safety
+///
+/// The sets in this crate use references to their own items as keys, with a
+/// `'static` lifetime they don't really have: a key is only valid while its
+/// pair is in the map, and a key passed to any other method, such as [`get`],
+/// [`remove`] or [`OrderedMap::neighbors`], only for the duration of the
+/// call. An implementation must therefore not use a key
+/// at any other time, e.g. by keeping a copy of it after its pair has been
+/// removed or replaced, or by handing one out. Comparing, hashing and
+/// dropping keys while their pairs are in the map is fine.
+///
+/// Each set drops its map before its items, so the keys are still valid
+/// when the map is dropped.
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use std::mem;
+/// use shawshank::{ArenaSet, Map, NewMap};
+///
+/// // An association list, which is fast enough for a handful of items.
+/// struct AssocList<K, V>(Vec<(K, V)>);
+///
+/// // Safety: keys are only compared while their pair is in the list, and
+/// // never copied out of it.
+/// unsafe impl<K: Eq, V> Map for AssocList<K, V> {
+///     type Key = K;
+///     type Value = V;
+///
+///     fn len(&self) -> usize {
+///         self.0.len()
+///     }
+///
+///     fn insert(&mut self, k: K, v: V) -> Option<V> {
+///         match self.0.iter_mut().find(|pair| pair.0 == k) {
+///             Some(pair) => Some(mem::replace(&mut pair.1, v)),
+///             None => {
+///                 self.0.push((k, v));
+///                 None
+///             }
+///         }
+///     }
+///
+///     fn get(&self, k: K) -> Option<&V> {
+///         self.0.iter().find(|pair| pair.0 == k).map(|pair| &pair.1)
+///     }
+///
+///     fn remove(&mut self, k: K) -> Option<V> {
+///         let ix = self.0.iter().position(|pair| pair.0 == k)?;
+///         Some(self.0.swap_remove(ix).1)
+///     }
+///
+///     fn shrink_to_fit(&mut self) {
+///         self.0.shrink_to_fit();
+///     }
+///
+///     fn clear(&mut self) {
+///         self.0.clear();
+///     }
+/// }
+///
+/// impl<K: Eq, V> NewMap for AssocList<K, V> {
+///     fn new() -> Self {
+///         AssocList(Vec::new())
+///     }
+///
+///     fn with_capacity(capacity: usize) -> Self {
+///         AssocList(Vec::with_capacity(capacity))
+///     }
+/// }
+///
+/// let mut p = ArenaSet::<String, usize, AssocList<&'static str, usize>>::new().unwrap();
+/// assert_eq!(p.intern("hello"), Ok(0));
+/// assert_eq!(p.intern("world"), Ok(1));
+/// assert_eq!(p.disintern(0), Ok("hello".into()));
+/// assert_eq!(p.get_id("hello"), None);
+/// let remap: BTreeMap<_, _> = p.shrink();
+/// assert_eq!(remap[&1], 0);
+/// assert_eq!(p.get_id("world"), Some(0));
+/// assert_eq!(p.resolve(0), Ok("world"));
+/// ```
+///
 /// [`ArenaSet`]: struct.ArenaSet.html
-pub trait Map {
+/// [`NewMap`]: trait.NewMap.html
+/// [`ArenaSet::with_map`]: struct.ArenaSet.html#method.with_map
+/// [`get`]: #tymethod.get
+/// [`remove`]: #tymethod.remove
+/// [`OrderedMap::neighbors`]: trait.OrderedMap.html#tymethod.neighbors
+pub unsafe trait Map {
     type Key;
     type Value;
 
-    /// Create an empty map.
-    ///
-    /// This is required for `ArenaSet` to function properly.
-    fn new() -> Self;
-
-    /// Create an empty map with a capacity hint.
-    ///
-    /// Not all implementations may support this, making it equivalent to
-    /// `Map::new`.
-    fn with_capacity(usize) -> Self;
-
     /// Get the number of pairs in the map.
     fn len(&self) -> usize;
 
+    /// Reserve room for at least `additional` more pairs.
+    ///
+    /// Not all implementations may support this, making it a no-op.
+    fn reserve(&mut self, _additional: usize) {}
+
     /// Insert a key-value pair. If there was already an entry for the key,
     /// it gets replaced, and the previous returned.
     ///
@@ -47,24 +138,32 @@ pub trait Map {
     ///
     /// Not all implementations may support this, making it a no-op.
     fn shrink_to_fit(&mut self);
-}
 
-impl<K: Eq + Hash, V, H: Default + BuildHasher> Map for HashMap<K, V, H> {
-    type Key = K;
-    type Value = V;
+    /// Remove all pairs.
+    fn clear(&mut self);
 
-    fn new() -> Self {
-        HashMap::default()
+    /// Estimate the number of bytes allocated by the map itself, not
+    /// counting anything the keys or values own.
+    ///
+    /// By default, this is the size of all pairs, which underestimates any
+    /// real implementation.
+    fn estimated_heap_size(&self) -> usize {
+        self.len() * (mem::size_of::<Self::Key>() + mem::size_of::<Self::Value>())
     }
+}
 
-    fn with_capacity(capacity: usize) -> Self {
-        HashMap::with_capacity_and_hasher(capacity, Default::default())
-    }
+unsafe impl<K: Eq + Hash, V, H: BuildHasher> Map for HashMap<K, V, H> {
+    type Key = K;
+    type Value = V;
 
     fn len(&self) -> usize {
         self.len()
     }
 
+    fn reserve(&mut self, additional: usize) {
+        self.reserve(additional);
+    }
+
     fn insert(&mut self, k: K, v: V) -> Option<V> {
         self.insert(k, v)
     }
@@ -80,19 +179,20 @@ impl<K: Eq + Hash, V, H: Default + BuildHasher> Map for HashMap<K, V, H> {
     fn shrink_to_fit(&mut self) {
         self.shrink_to_fit();
     }
-}
-
-impl<K: Eq + Ord, V> Map for BTreeMap<K, V> {
-    type Key = K;
-    type Value = V;
 
-    fn new() -> Self {
-        BTreeMap::new()
+    fn clear(&mut self) {
+        self.clear();
     }
 
-    fn with_capacity(_: usize) -> Self {
-        BTreeMap::new()
+    fn estimated_heap_size(&self) -> usize {
+        // every bucket holds a pair, plus a byte of control metadata
+        self.capacity() * (mem::size_of::<(K, V)>() + 1)
     }
+}
+
+unsafe impl<K: Eq + Ord, V> Map for BTreeMap<K, V> {
+    type Key = K;
+    type Value = V;
 
     fn len(&self) -> usize {
         self.len()
@@ -111,4 +211,200 @@ impl<K: Eq + Ord, V> Map for BTreeMap<K, V> {
     }
 
     fn shrink_to_fit(&mut self) {}
+
+    fn clear(&mut self) {
+        self.clear();
+    }
+}
+
+/// A [`Map`] that can be created from nothing, as needed by the
+/// constructors of [`ArenaSet`], and for the maps from old IDs to new ones
+/// returned by methods like [`ArenaSet::shrink`].
+///
+/// [`Map`]: trait.Map.html
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`ArenaSet::shrink`]: struct.ArenaSet.html#method.shrink
+pub trait NewMap: Map {
+    /// Create an empty map.
+    fn new() -> Self;
+
+    /// Create an empty map with a capacity hint.
+    ///
+    /// Not all implementations may support this, making it equivalent to
+    /// `NewMap::new`.
+    fn with_capacity(capacity: usize) -> Self;
+}
+
+impl<K: Eq + Hash, V, H: BuildHasher + Default> NewMap for HashMap<K, V, H> {
+    fn new() -> Self {
+        HashMap::default()
+    }
+
+    fn with_capacity(capacity: usize) -> Self {
+        HashMap::with_capacity_and_hasher(capacity, Default::default())
+    }
+}
+
+impl<K: Eq + Ord, V> NewMap for BTreeMap<K, V> {
+    fn new() -> Self {
+        BTreeMap::new()
+    }
+
+    fn with_capacity(_: usize) -> Self {
+        BTreeMap::new()
+    }
+}
+
+/// A [`Map`] whose keys are ordered, as needed to keep IDs in the order of
+/// their items with [`ArenaSet::sorted`].
+///
+/// # Safety
+///
+/// The key passed to [`neighbors`] is subject to the same contract as those
+/// passed to the methods of [`Map`]: it must not be used after the call
+/// returns.
+///
+/// [`Map`]: trait.Map.html
+/// [`ArenaSet::sorted`]: struct.ArenaSet.html#method.sorted
+/// [`neighbors`]: #tymethod.neighbors
+pub unsafe trait OrderedMap: Map {
+    /// Get the values of the greatest key less than `key`, and of the least
+    /// key greater than it.
+    fn neighbors(&self, key: Self::Key) -> (Option<&Self::Value>, Option<&Self::Value>);
+}
+
+unsafe impl<K: Eq + Ord, V> OrderedMap for BTreeMap<K, V> {
+    fn neighbors(&self, k: K) -> (Option<&V>, Option<&V>) {
+        let before = self.range((Bound::Unbounded, Bound::Excluded(&k))).next_back().map(|(_, v)| v);
+        let after = self.range((Bound::Excluded(&k), Bound::Unbounded)).next().map(|(_, v)| v);
+        (before, after)
+    }
+}
+
+/// The interface shared by [`ArenaSet`] and [`StadiumSet`], so that generic
+/// code can accept either.
+///
+/// The methods behave exactly like the inherent ones of the same name, which
+/// take precedence when the concrete type is known.
+///
+/// ```
+/// use shawshank::{Error, Interner};
+///
+/// fn intern_all<P: Interner<Item = str>>(p: &mut P, words: &[&'static str]) -> Result<Vec<P::Id>, Error>
+///     where P::Input: From<&'static str> {
+///     words.iter().map(|&word| Interner::intern(p, word)).collect()
+/// }
+///
+/// let mut p = shawshank::string_arena_set();
+/// assert_eq!(intern_all(&mut p, &["a", "b", "a"]), Ok(vec![0, 1, 0]));
+/// assert_eq!(Interner::resolve(&p, 1), Ok("b"));
+///
+/// let mut p = shawshank::string_stadium_set();
+/// assert_eq!(intern_all(&mut p, &["a", "b", "a"]), Ok(vec![0, 1, 0]));
+/// assert_eq!(Interner::count(&p), 2);
+/// ```
+///
+/// [`ArenaSet`]: struct.ArenaSet.html
+/// [`StadiumSet`]: struct.StadiumSet.html
+pub trait Interner {
+    /// The type of interned items, as they're looked up (e.g. `str`).
+    type Item: ? Sized;
+
+    /// The type that's converted from the arguments of `intern`.
+    type Input;
+
+    /// The type of interned items, as they're owned (e.g. `String`).
+    type Owned;
+
+    /// The "ID" type to uniquely resolve interned items.
+    type Id;
+
+    /// Intern an item, receiving an ID that can later be used to resolve the
+    /// original.
+    fn intern<Q>(&mut self, item: Q) -> Result<Self::Id, Error>
+        where Q: Borrow<Self::Item>,
+              Self::Input: From<Q>;
+
+    /// Resolve an item by its unique ID.
+    fn resolve<U: Borrow<Self::Id>>(&self, id: U) -> Result<&Self::Item, Error>;
+
+    /// Disintern an item by its unique ID.
+    fn disintern<U: Borrow<Self::Id>>(&mut self, id: U) -> Result<Self::Owned, Error>;
+
+    /// Shrink the internal data structures by re-using ID of disinterned
+    /// items. Returns a map from the old IDs to the new ones.
+    fn shrink<T: NewMap<Key = Self::Id, Value = Self::Id>>(&mut self) -> T;
+
+    /// Get the number of interned items.
+    fn count(&self) -> usize;
+}
+
+/// Types that can report how many bytes they own on the heap, for
+/// [`ArenaSet::memory_usage`].
+///
+/// ```
+/// use shawshank::HeapSize;
+///
+/// assert_eq!(String::with_capacity(10).heap_size(), 10);
+/// assert_eq!(vec![String::from("abc")].heap_size(), 24 + 3);
+/// ```
+///
+/// [`ArenaSet::memory_usage`]: struct.ArenaSet.html#method.memory_usage
+pub trait HeapSize {
+    /// Get the number of bytes owned on the heap, not including the size of
+    /// `self`.
+    fn heap_size(&self) -> usize;
+}
+
+macro_rules! impl_heap_size_none {
+    ( $( $t:ty ),* ) => { $(
+        impl HeapSize for $t {
+            #[inline]
+            fn heap_size(&self) -> usize {
+                0
+            }
+        }
+    )* }
+}
+
+impl_heap_size_none!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64, bool, char, str);
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize> HeapSize for [T] {
+    fn heap_size(&self) -> usize {
+        self.iter().map(HeapSize::heap_size).sum()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * mem::size_of::<T>() + self[..].heap_size()
+    }
+}
+
+impl<T: ? Sized + HeapSize> HeapSize for Box<T> {
+    fn heap_size(&self) -> usize {
+        mem::size_of_val(&**self) + (**self).heap_size()
+    }
+}
+
+/// Counts the whole allocation, including the reference counts, even though
+/// it may be shared.
+impl<T: ? Sized + HeapSize> HeapSize for Rc<T> {
+    fn heap_size(&self) -> usize {
+        2 * mem::size_of::<usize>() + mem::size_of_val(&**self) + (**self).heap_size()
+    }
+}
+
+/// Counts the whole allocation, including the reference counts, even though
+/// it may be shared.
+impl<T: ? Sized + HeapSize> HeapSize for Arc<T> {
+    fn heap_size(&self) -> usize {
+        2 * mem::size_of::<usize>() + mem::size_of_val(&**self) + (**self).heap_size()
+    }
 }